@@ -0,0 +1,84 @@
+//! Authentication against the harness's private cluster, which has a role for
+//! each pg_hba.conf method; see [`Method`]. The tests here run psql
+//! non-interactively with the password in `PGPASSWORD`, or none at all, and
+//! check which methods let it in. Password files and the prompts of a PTY
+//! session have suites of their own.
+
+mod password_file;
+mod terminal_tty;
+
+use crate::common::cluster::{Cluster, Method, PASSWORD};
+use std::error::Error;
+use std::process::Output;
+
+/// Connects as the role of `method`, with `password` in `PGPASSWORD` if any,
+/// and asks the server who it is.
+fn connect(method: Method, password: Option<&str>, extra: &[&str]) -> Result<Output, Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    let mut command = if method == Method::Cert {
+        cluster.psql_tls(method.role(), true)
+    } else {
+        cluster.psql(method.role())
+    };
+    if let Some(password) = password {
+        command.env("PGPASSWORD", password);
+    }
+    Ok(command.args(extra).args(["-At", "-c", "SELECT current_user;"]).output()?)
+}
+
+#[test]
+fn test_auth_methods() -> Result<(), Box<dyn Error>> {
+    for method in Method::ALL {
+        let password = method.needs_password().then_some(PASSWORD);
+        let output = connect(method, password, &[])?;
+        expect_exit_status!(output, 0);
+        verify!(&output.stdout, &format!("{}\n", method.role()));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_auth_wrong_password() -> Result<(), Box<dyn Error>> {
+    for method in Method::ALL.into_iter().filter(|method| method.needs_password()) {
+        let output = connect(method, Some("wrong"), &[])?;
+        expect_exit_status!(output, 2);
+        verify_contains!(
+            &output.stderr,
+            &format!("password authentication failed for user \"{}\"", method.role())
+        );
+    }
+    Ok(())
+}
+
+/// Without a password and with `-w`, psql gives up rather than asking for one.
+#[test]
+fn test_auth_no_password_prompt() -> Result<(), Box<dyn Error>> {
+    for method in Method::ALL.into_iter().filter(|method| method.needs_password()) {
+        let output = connect(method, None, &["-w"])?;
+        expect_exit_status!(output, 2);
+        verify_contains!(&output.stderr, "fe_sendauth: no password supplied");
+    }
+    Ok(())
+}
+
+/// Methods that don't ask for a password ignore one that is given.
+#[test]
+fn test_auth_unused_password() -> Result<(), Box<dyn Error>> {
+    for method in Method::ALL.into_iter().filter(|method| !method.needs_password()) {
+        let output = connect(method, Some("wrong"), &["-w"])?;
+        expect_exit_status!(output, 0);
+        verify!(&output.stdout, &format!("{}\n", method.role()));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_auth_cert_without_certificate() -> Result<(), Box<dyn Error>> {
+    let output = Cluster::shared()
+        .psql_tls(Method::Cert.role(), false)
+        .args(["-w", "-c", "SELECT 1;"])
+        .output()?;
+    expect_exit_status!(output, 2);
+    verify_contains!(&output.stderr, "connection requires a valid client certificate");
+    Ok(())
+}
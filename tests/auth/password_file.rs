@@ -0,0 +1,109 @@
+//! Passwords from a password file rather than the environment, named by
+//! `PGPASSFILE` or found as `.pgpass` in the home directory. Every run has
+//! `-w`, so a password file that isn't used fails the connection instead of
+//! waiting at a prompt.
+
+use crate::common::cluster::{Cluster, Method, DATABASE, PASSWORD};
+use crate::common::*;
+use std::error::Error;
+use std::fs::{self, Permissions};
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// A psql as the scram role connecting over the socket, with `-w`.
+fn psql(cluster: &Cluster) -> Command {
+    let mut command = cluster.psql(Method::ScramSha256.role());
+    command.args(["-w", "-At", "-c", "SELECT current_user;"]);
+    command
+}
+
+/// Writes `lines` to a password file in the test's directory with `mode`.
+fn password_file(name: &str, lines: &[String], mode: u32) -> Result<PathBuf, Box<dyn Error>> {
+    let path = test_dir().join(name);
+    fs::write(&path, lines.join("\n") + "\n")?;
+    fs::set_permissions(&path, Permissions::from_mode(mode))?;
+    Ok(path)
+}
+
+/// The line of a password file for the cluster's socket and `role`.
+fn entry(cluster: &Cluster, port: u16, role: &str, password: &str) -> String {
+    format!("{}:{}:{}:{}:{}", cluster.data_dir().display(), port, DATABASE, role, password)
+}
+
+fn connected(output: &Output) {
+    expect_exit_status!(output, 0);
+    verify!(&output.stdout, &format!("{}\n", Method::ScramSha256.role()));
+}
+
+#[test]
+fn test_password_file_pgpassfile() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    let path = password_file("pgpass", &[format!("*:*:*:{}:{}", Method::ScramSha256.role(), PASSWORD)], 0o600)?;
+    connected(&psql(cluster).env("PGPASSFILE", &path).output()?);
+    Ok(())
+}
+
+/// Lines for another port, database or role are skipped, and the first line
+/// that matches is used even if a later one would work.
+#[test]
+fn test_password_file_first_match() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    let role = Method::ScramSha256.role();
+    let lines = [
+        "# comments are skipped".to_string(),
+        entry(cluster, cluster.port + 1, role, "wrong port"),
+        format!("{}:{}:other:{}:wrong database", cluster.data_dir().display(), cluster.port, role),
+        entry(cluster, cluster.port, Method::Md5.role(), "wrong role"),
+        entry(cluster, cluster.port, role, PASSWORD),
+        entry(cluster, cluster.port, role, "too late"),
+    ];
+    let path = password_file("pgpass", &lines, 0o600)?;
+    connected(&psql(cluster).env("PGPASSFILE", &path).output()?);
+
+    let path = password_file("pgpass_wrong_first", &[lines[5].clone(), lines[4].clone()], 0o600)?;
+    let output = psql(cluster).env("PGPASSFILE", &path).output()?;
+    expect_exit_status!(output, 2);
+    verify_contains!(&output.stderr, &format!("password authentication failed for user \"{}\"", role));
+    verify_contains!(&output.stderr, &format!("password retrieved from file \"{}\"", path.display()));
+    Ok(())
+}
+
+/// A password file others can read is ignored, with a warning.
+#[test]
+fn test_password_file_permissions() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    let path = password_file("pgpass", &[format!("*:*:*:*:{}", PASSWORD)], 0o644)?;
+    let output = psql(cluster).env("PGPASSFILE", &path).output()?;
+    expect_exit_status!(output, 2);
+    verify_contains!(
+        &output.stderr,
+        &format!(
+            "WARNING: password file \"{}\" has group or world access; permissions should be u=rw (0600) or less",
+            path.display()
+        )
+    );
+    verify_contains!(&output.stderr, "fe_sendauth: no password supplied");
+    Ok(())
+}
+
+/// `PGPASSWORD` takes precedence, so the file's password is never tried.
+#[test]
+fn test_password_file_environment_first() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    let path = password_file("pgpass", &[format!("*:*:*:*:{}", PASSWORD)], 0o600)?;
+    let output = psql(cluster).env("PGPASSFILE", &path).env("PGPASSWORD", "wrong").output()?;
+    expect_exit_status!(output, 2);
+    verify_contains!(&output.stderr, "password authentication failed");
+    Ok(())
+}
+
+/// Without `PGPASSFILE`, libpq reads `.pgpass` in the home directory.
+#[test]
+fn test_password_file_home() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    let home = test_dir();
+    password_file(".pgpass", &[format!("*:*:*:*:{}", PASSWORD)], 0o600)?;
+    connected(&psql(cluster).env_remove("PGPASSFILE").env("HOME", &home).output()?);
+    Ok(())
+}
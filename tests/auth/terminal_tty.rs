@@ -0,0 +1,195 @@
+//! Password prompts in a PTY session. psql asks on `/dev/tty` with echo off,
+//! so a script or COPY data on stdin is left alone while the password is
+//! typed, including when a `\c` halfway through the script asks again.
+
+use crate::common::cluster::{Cluster, Method, DATABASE, PASSWORD, SUPERUSER};
+use crate::common::*;
+use expectrl::Eof;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::NamedTempFile;
+use uuid::Uuid;
+
+fn password_prompt(role: &str) -> String {
+    format!("Password for user {}: ", role)
+}
+
+/// Runs `command` with its stdin redirected from `path` by a shell, since a
+/// PTY session has the terminal as its stdin.
+fn with_stdin(command: Command, path: &Path) -> Command {
+    let mut shell = Command::new("sh");
+    shell
+        .args(["-c", r#"exec "$@" < "$0""#])
+        .arg(path)
+        .arg(command.get_program())
+        .args(command.get_args());
+    for (name, value) in command.get_envs() {
+        match value {
+            Some(value) => shell.env(name, value),
+            None => shell.env_remove(name),
+        };
+    }
+    shell
+}
+
+fn start(command: Command) -> Result<(NamedTempFile, PtySession), Box<dyn Error>> {
+    let temp_file = NamedTempFile::new()?;
+    let mut session = spawn_session(command, temp_file.as_file())?;
+    set_timeout(&mut session, suite_timeout!());
+    Ok((temp_file, session))
+}
+
+#[test]
+fn test_auth_prompt() -> Result<(), Box<dyn Error>> {
+    for method in Method::ALL.into_iter().filter(|method| method.needs_password()) {
+        let (temp_file, mut session) = start(Cluster::shared().psql(method.role()))?;
+
+        expect_prompt!(&mut session, &password_prompt(method.role()), &temp_file);
+        session.send_line(PASSWORD)?;
+        expect_prompt!(&mut session, PROMPT1, &temp_file);
+        // Echo was off while the password was typed.
+        assert!(!session.get_stream().screen().contents().contains(PASSWORD));
+        session.send_line("SELECT current_user;")?;
+        expect_screen!(&mut session, method.role(), &temp_file);
+        session.send_line("\\q")?;
+        session.expect(Eof)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_auth_prompt_wrong_password() -> Result<(), Box<dyn Error>> {
+    let role = Method::ScramSha256.role();
+    let (temp_file, mut session) = start(Cluster::shared().psql(role))?;
+
+    expect_prompt!(&mut session, &password_prompt(role), &temp_file);
+    session.send_line("wrong")?;
+    expect_screen!(
+        &mut session,
+        &format!("password authentication failed for user \"{}\"", role),
+        &temp_file
+    );
+    session.expect(Eof)?;
+    Ok(())
+}
+
+/// `-W` asks before connecting, even when the server wouldn't ask at all,
+/// and without naming the user.
+#[test]
+fn test_auth_prompt_forced() -> Result<(), Box<dyn Error>> {
+    let mut command = Cluster::shared().psql(Method::Trust.role());
+    command.arg("-W");
+    let (temp_file, mut session) = start(command)?;
+
+    expect_prompt!(&mut session, "Password: ", &temp_file);
+    session.send_line("anything")?;
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+    Ok(())
+}
+
+/// `-w` never asks, even with a terminal to ask on.
+#[test]
+fn test_auth_prompt_never() -> Result<(), Box<dyn Error>> {
+    let mut command = Cluster::shared().psql(Method::ScramSha256.role());
+    command.arg("-w");
+    let (temp_file, mut session) = start(command)?;
+
+    expect_screen!(&mut session, "fe_sendauth: no password supplied", &temp_file);
+    session.expect(Eof)?;
+    Ok(())
+}
+
+/// `\password` asks for the new password twice, and the role can log in with
+/// it afterwards.
+#[test]
+fn test_auth_password_command() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    let role = format!("auth_{}", Uuid::new_v4().simple());
+    let output = cluster.query(&format!("CREATE ROLE {} LOGIN;", role))?;
+    expect_exit_status!(output, 0);
+    let (temp_file, mut session) = start(cluster.psql(SUPERUSER))?;
+
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line(format!("\\password {}", role))?;
+    expect_prompt!(&mut session, &format!("Enter new password for user \"{}\": ", role), &temp_file);
+    session.send_line("first")?;
+    expect_prompt!(&mut session, "Enter it again: ", &temp_file);
+    session.send_line("second")?;
+    expect_screen!(&mut session, "Passwords didn't match.", &temp_file);
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+
+    session.send_line(format!("\\password {}", role))?;
+    expect_prompt!(&mut session, &format!("Enter new password for user \"{}\": ", role), &temp_file);
+    session.send_line(PASSWORD)?;
+    expect_prompt!(&mut session, "Enter it again: ", &temp_file);
+    session.send_line(PASSWORD)?;
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+
+    let output = cluster
+        .psql(&role)
+        .env("PGPASSWORD", PASSWORD)
+        .args(["-w", "-At", "-c", "SELECT current_user;"])
+        .output()?;
+    expect_exit_status!(output, 0);
+    verify!(&output.stdout, &format!("{}\n", role));
+    let output = cluster.query(&format!("DROP ROLE {};", role))?;
+    expect_exit_status!(output, 0);
+    Ok(())
+}
+
+/// A script on stdin with COPY data in it, whose table is `table`.
+fn copy_script(table: Uuid, before: &str) -> String {
+    format!(
+        "{before}CREATE TABLE \"{table}\" (a int, b int);\n\
+         COPY \"{table}\" FROM STDIN;\n1\t2\n3\t4\n\\.\n\
+         SELECT sum(a + b) AS total FROM \"{table}\";\n\
+         DROP TABLE \"{table}\";\n"
+    )
+}
+
+/// psql asks for the password on the terminal before it reads the script and
+/// its COPY data from stdin, so none of it is taken for the password.
+#[test]
+fn test_auth_prompt_copy_stdin() -> Result<(), Box<dyn Error>> {
+    let role = Method::ScramSha256.role();
+    let script = test_dir().join("script.sql");
+    fs::write(&script, copy_script(Uuid::new_v4(), ""))?;
+    let (temp_file, mut session) = start(with_stdin(Cluster::shared().psql(role), &script))?;
+
+    expect_prompt!(&mut session, &password_prompt(role), &temp_file);
+    session.send_line(PASSWORD)?;
+    expect_screen!(&mut session, "COPY 2", &temp_file);
+    expect_screen!(&mut session, "10", &temp_file);
+    expect_screen!(&mut session, "DROP TABLE", &temp_file);
+    session.expect(Eof)?;
+    Ok(())
+}
+
+/// A `\c` in the middle of a script asks on the terminal, and the COPY data
+/// after it still comes from stdin.
+#[test]
+fn test_auth_reconnect_prompt_copy_stdin() -> Result<(), Box<dyn Error>> {
+    let role = Method::ScramSha256.role();
+    let script = test_dir().join("script.sql");
+    let reconnect = format!("SELECT current_user;\n\\c {} {}\nSELECT current_user;\n", DATABASE, role);
+    fs::write(&script, copy_script(Uuid::new_v4(), &reconnect))?;
+    let (temp_file, mut session) = start(with_stdin(Cluster::shared().psql(Method::Trust.role()), &script))?;
+
+    expect_prompt!(&mut session, &password_prompt(role), &temp_file);
+    session.send_line(PASSWORD)?;
+    expect_screen!(&mut session, "COPY 2", &temp_file);
+    expect_screen!(&mut session, "10", &temp_file);
+    expect_screen!(&mut session, "DROP TABLE", &temp_file);
+    let screen = session.get_stream().screen().contents();
+    let before = screen.find(&format!("\n {}", Method::Trust.role())).expect("no current_user before \\c");
+    let after = screen.find(&format!("\n {}", role)).expect("no current_user after \\c");
+    assert!(before < after, "current_user after \\c printed before the one from before it:\n{}", screen);
+    session.expect(Eof)?;
+    Ok(())
+}
@@ -0,0 +1,3 @@
+mod terminal_file;
+mod terminal_stdin;
+mod terminal_tty;
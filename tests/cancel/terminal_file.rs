@@ -0,0 +1,74 @@
+use crate::common::*;
+use expectrl::Eof;
+use std::error::Error;
+use std::io::Write;
+use std::thread;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Waits until a backend is copying into `table`, so the ^C lands while the
+/// COPY streams rather than before psql has even sent it.
+fn wait_for_copy(table: &Uuid) -> Result<(), Box<dyn Error>> {
+    let sql = format!(r#"SELECT count(*) FROM pg_stat_progress_copy WHERE relid = '"{}"'::regclass;"#, table);
+    let deadline = Instant::now() + suite_timeout!();
+    loop {
+        let output = run_cmd("psql", &["-XAtc", &sql])?;
+        expect_exit_status!(output, 0);
+        if output.stdout != b"0\n" {
+            return Ok(());
+        }
+        if Instant::now() > deadline {
+            return Err(format!("no COPY into {} started in time", table).into());
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+fn cancel() -> Result<(), Box<dyn Error>> {
+    let env = get_test_environment();
+    let test_table = Uuid::new_v4();
+    let output = run_cmd("psql", &["-c", &format!(r#"CREATE TABLE "{}" (c1 int8, c2 int8);"#, test_table)])?;
+    expect_create_table!(output);
+
+    // Enough rows to keep psql streaming CopyData long after the cancel,
+    // with a trigger slowing the server down so the COPY cannot finish first.
+    let large_file = test_dir().join(format!("{}.text", test_table));
+    write_series_file(&large_file, 100000)?;
+    let output = run_cmd("psql", &["-c", &format!(
+        r#"CREATE FUNCTION "{0}"() RETURNS trigger LANGUAGE plpgsql AS $$BEGIN PERFORM pg_sleep(0.001); RETURN NEW; END$$;
+           CREATE TRIGGER slow BEFORE INSERT ON "{0}" FOR EACH ROW EXECUTE FUNCTION "{0}"();"#,
+        test_table
+    )])?;
+    expect_exit_status!(output, 0);
+
+    let temp_file = tempfile::NamedTempFile::new()?;
+    let log_file = temp_file.as_file();
+
+    let mut session = spawn_session(psql_command(), log_file)?;
+
+    set_timeout(&mut session, suite_timeout!());
+
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line(format!(r#"\copy "{}" from '{}'"#, test_table, large_file.display()))?;
+    wait_for_copy(&test_table)?;
+    write!(session, "\x03")?;
+    expect!(&mut session, "canceling statement due to user request", &temp_file);
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line(format!(r#"\copy "{}" from '{}'"#, test_table, env.file_path_text))?;
+    expect!(&mut session, "COPY 2", &temp_file);
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+
+    // None of the rows streamed before the cancel may survive it.
+    let output = run_cmd("psql", &["-c", &format!(r#"SELECT * FROM "{}";"#, test_table)])?;
+    expect_result_set!(output);
+    let output = run_cmd("psql", &["-c", &format!(r#"DROP TABLE "{0}"; DROP FUNCTION "{0}"();"#, test_table)])?;
+    expect_exit_status!(output, 0);
+    Ok(())
+}
+
+#[test]
+fn test_psql_copy_cancel() -> Result<(), Box<dyn Error>> {
+    rerun(cancel)
+}
@@ -0,0 +1,45 @@
+use crate::common::*;
+use expectrl::Eof;
+use std::error::Error;
+use std::io::Write;
+use uuid::Uuid;
+
+fn cancel() -> Result<(), Box<dyn Error>> {
+    let env = get_test_environment();
+    let test_table = Uuid::new_v4();
+    let output = run_cmd("psql", &["-c", &format!(r#"CREATE TABLE "{}" (c1 int8, c2 int8);"#, test_table)])?;
+    expect_create_table!(output);
+
+    let temp_file = tempfile::NamedTempFile::new()?;
+    let log_file = temp_file.as_file();
+
+    let mut session = spawn_session(psql_command(), log_file)?;
+
+    set_timeout(&mut session, suite_timeout!());
+
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line(format!(r#"\copy "{}" from '/dev/tty'"#, test_table))?;
+    expect_prompt!(&mut session, PROMPT3, &temp_file);
+    session.send_line("1\t2")?;
+    expect_prompt!(&mut session, PROMPT3, &temp_file);
+    write!(session, "\x03")?;
+    expect!(&mut session, "COPY from stdin failed: canceled by user", &temp_file);
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line(format!(r#"\copy "{}" from '{}'"#, test_table, env.file_path_text))?;
+    expect!(&mut session, "COPY 2", &temp_file);
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+
+    // Had the canceled COPY kept its row, (1, 2) would show up twice here.
+    let output = run_cmd("psql", &["-c", &format!(r#"SELECT * FROM "{}";"#, test_table)])?;
+    expect_result_set!(output);
+    let output = run_cmd("psql", &["-c", &format!(r#"DROP TABLE "{}";"#, test_table)])?;
+    expect_drop_table!(output);
+    Ok(())
+}
+
+#[test]
+fn test_psql_copy_cancel() -> Result<(), Box<dyn Error>> {
+    rerun(cancel)
+}
@@ -0,0 +1,152 @@
+//! A minimal frontend for the test server, speaking protocol version 3
+//! directly. It loads COPY data without going through psql, so that what psql
+//! does to the data can be told apart from what the server does with it.
+//!
+//! It connects as the same user to the same database as psql, with the
+//! calling test's schema, and only supports servers that let it in without a
+//! password.
+
+use crate::common::isolation;
+use crate::common::proxy::{upstream, Stream};
+use crate::common::run_cmd;
+use crate::common::wire::{self, Message};
+use once_cell::sync::OnceCell;
+use std::io::{self, BufReader, Write};
+
+const PROTOCOL_VERSION: u32 = 3 << 16;
+
+/// CopyData payloads are at most this long, like psql's.
+const CHUNK: usize = 8192;
+
+/// How the server answered a query: the CommandComplete tag, e.g. `"COPY 2"`,
+/// or the ErrorResponse message.
+pub type Reply = Result<String, String>;
+
+pub struct Client {
+    reader: BufReader<Stream>,
+    writer: Stream,
+}
+
+/// The user and database psql connects to, from the server itself.
+fn login() -> &'static (String, String) {
+    static LOGIN: OnceCell<(String, String)> = OnceCell::new();
+    LOGIN.get_or_init(|| {
+        let output = run_cmd("psql", &["-XAtc", "SELECT current_user || '|' || current_database();"]).unwrap();
+        assert!(output.status.success(), "could not look up the user and database of the test server");
+        let login = String::from_utf8(output.stdout).unwrap();
+        let (user, database) = login.trim().split_once('|').unwrap();
+        (user.to_string(), database.to_string())
+    })
+}
+
+impl Client {
+    pub fn connect() -> io::Result<Self> {
+        let writer = Stream::connect(upstream())?;
+        let mut client = Self {
+            reader: BufReader::new(writer.try_clone()?),
+            writer,
+        };
+        let (user, database) = login();
+        let mut body = PROTOCOL_VERSION.to_be_bytes().to_vec();
+        let options = format!("-c search_path={}", isolation::schema());
+        for (name, value) in [("user", user), ("database", database), ("options", &options)] {
+            body.extend_from_slice(name.as_bytes());
+            body.push(0);
+            body.extend_from_slice(value.as_bytes());
+            body.push(0);
+        }
+        body.push(0);
+        client.writer.write_all(&Message { tag: None, body }.to_bytes())?;
+        loop {
+            let message = client.read()?;
+            match message.tag {
+                Some(b'R') if message.body[..4] != [0, 0, 0, 0] => {
+                    let method = u32::from_be_bytes(message.body[..4].try_into().unwrap());
+                    return Err(io::Error::other(format!("authentication method {} is not supported", method)));
+                }
+                Some(wire::ERROR_RESPONSE) => return Err(io::Error::other(error_message(&message))),
+                Some(b'Z') => return Ok(client),
+                _ => {}
+            }
+        }
+    }
+
+    /// Runs `sql` as a simple query.
+    pub fn query(&mut self, sql: &str) -> io::Result<Reply> {
+        self.send_query(sql)?;
+        self.finish(None)
+    }
+
+    /// Runs a `COPY ... FROM STDIN` and sends it `data`, in chunks like psql.
+    pub fn copy_in(&mut self, sql: &str, data: &[u8]) -> io::Result<Reply> {
+        self.send_query(sql)?;
+        loop {
+            let message = self.read()?;
+            match message.tag {
+                Some(b'G') => break,
+                Some(wire::ERROR_RESPONSE) => return self.finish(Some(Err(error_message(&message)))),
+                _ => {}
+            }
+        }
+        let mut out = Vec::new();
+        for chunk in data.chunks(CHUNK) {
+            out.extend(Message::new(wire::COPY_DATA, chunk.to_vec()).to_bytes());
+        }
+        out.extend(Message::new(wire::COPY_DONE, Vec::new()).to_bytes());
+        self.writer.write_all(&out)?;
+        self.finish(None)
+    }
+
+    /// Runs a `COPY ... TO STDOUT` and returns its data.
+    pub fn copy_out(&mut self, sql: &str) -> io::Result<(Vec<u8>, Reply)> {
+        self.send_query(sql)?;
+        let mut data = Vec::new();
+        loop {
+            let message = self.read()?;
+            match message.tag {
+                Some(wire::COPY_DATA) => data.extend(message.body),
+                Some(wire::COPY_DONE) => return Ok((data, self.finish(None)?)),
+                Some(wire::ERROR_RESPONSE) => return Ok((data, self.finish(Some(Err(error_message(&message))))?)),
+                _ => {}
+            }
+        }
+    }
+
+    fn send_query(&mut self, sql: &str) -> io::Result<()> {
+        let body = [sql.as_bytes(), b"\0"].concat();
+        self.writer.write_all(&Message::new(wire::QUERY, body).to_bytes())
+    }
+
+    /// Reads up to ReadyForQuery, keeping the first error or tag.
+    fn finish(&mut self, mut reply: Option<Reply>) -> io::Result<Reply> {
+        loop {
+            let message = self.read()?;
+            match message.tag {
+                Some(wire::COMMAND_COMPLETE) if reply.is_none() => reply = Some(Ok(wire::cstring(&message.body))),
+                Some(wire::ERROR_RESPONSE) if !matches!(reply, Some(Err(_))) => {
+                    reply = Some(Err(error_message(&message)))
+                }
+                Some(b'Z') => return Ok(reply.unwrap_or_else(|| Ok(String::new()))),
+                _ => {}
+            }
+        }
+    }
+
+    fn read(&mut self) -> io::Result<Message> {
+        Message::read(&mut self.reader)?.ok_or_else(|| io::ErrorKind::UnexpectedEof.into())
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = self.writer.write_all(&Message::new(wire::TERMINATE, Vec::new()).to_bytes());
+    }
+}
+
+fn error_message(message: &Message) -> String {
+    wire::error_fields(&message.body)
+        .into_iter()
+        .find(|(code, _)| *code == b'M')
+        .map(|(_, message)| message)
+        .unwrap_or_default()
+}
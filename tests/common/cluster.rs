@@ -0,0 +1,479 @@
+//! A private cluster the harness sets up itself, for tests that need server
+//! configuration the shared test server can't be expected to have. It runs
+//! from a temporary directory, on a free port and on a Unix socket in its data
+//! directory, and is stopped at teardown.
+//!
+//! pg_hba.conf has one role per authentication [`Method`]. The cluster
+//! accepts TLS connections as well as plain ones, with a CA, a server
+//! certificate and a client certificate for the cert method made with the
+//! openssl command.
+//!
+//! A primary can also get a hot standby streaming from it, cloned with
+//! pg_basebackup.
+//!
+//! The server refuses to run as root. When the tests do, the server tools
+//! run as `PSQL_TEST_CLUSTER_USER`, `postgres` by default, through runuser.
+
+use crate::common::isolation;
+use crate::common::lifecycle::{self, DIR_PREFIX};
+use crate::common::psql_command;
+use once_cell::sync::OnceCell;
+use std::env;
+use std::fs::{self, Permissions};
+use std::io;
+use std::net::TcpListener;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The cluster's superuser, authenticated by trust over the socket and TCP.
+pub const SUPERUSER: &str = "tester";
+pub const DATABASE: &str = "postgres";
+/// The password of every role that has one.
+pub const PASSWORD: &str = "correct horse";
+
+/// How long a standby gets to catch up with its primary.
+const REPLAY_TIMEOUT: Duration = Duration::from_secs(10);
+
+extern "C" {
+    fn geteuid() -> u32;
+}
+
+/// A pg_hba.conf authentication method, each with a role of its own. The
+/// trust role can also connect over TCP.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    Trust,
+    Password,
+    Md5,
+    ScramSha256,
+    /// Over the Unix socket, for the operating system user running the tests.
+    Peer,
+    /// Over TCP with TLS, for a client certificate with the role's name.
+    Cert,
+}
+
+impl Method {
+    pub const ALL: [Method; 6] = [
+        Method::Trust,
+        Method::Password,
+        Method::Md5,
+        Method::ScramSha256,
+        Method::Peer,
+        Method::Cert,
+    ];
+
+    /// The role pg_hba.conf authenticates with this method.
+    pub fn role(self) -> &'static str {
+        match self {
+            Method::Trust => "auth_trust",
+            Method::Password => "auth_password",
+            Method::Md5 => "auth_md5",
+            Method::ScramSha256 => "auth_scram",
+            Method::Peer => "auth_peer",
+            Method::Cert => "auth_cert",
+        }
+    }
+
+    /// Whether the server asks the client for a password.
+    pub fn needs_password(self) -> bool {
+        matches!(self, Method::Password | Method::Md5 | Method::ScramSha256)
+    }
+
+    fn hba_line(self) -> String {
+        match self {
+            Method::Trust => format!("local all {0} trust\nhost all {0} 127.0.0.1/32 trust", self.role()),
+            Method::Password => format!("local all {} password", self.role()),
+            Method::Md5 => format!("local all {} md5", self.role()),
+            Method::ScramSha256 => format!("local all {} scram-sha-256", self.role()),
+            Method::Peer => format!("local all {} peer map=tester", self.role()),
+            Method::Cert => format!("hostssl all {} 127.0.0.1/32 cert", self.role()),
+        }
+    }
+}
+
+/// A running cluster. One of its own is stopped and removed when dropped;
+/// the shared ones at teardown.
+pub struct Cluster {
+    dir: PathBuf,
+    bindir: PathBuf,
+    /// Who initdb and pg_ctl run as, if not as the tests' own user.
+    run_as: Option<String>,
+    pub port: u16,
+}
+
+impl Drop for Cluster {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Cluster {
+    /// The cluster, started on first use.
+    pub fn shared() -> &'static Cluster {
+        static CLUSTER: OnceCell<Cluster> = OnceCell::new();
+        CLUSTER.get_or_init(|| {
+            let cluster = Cluster::start().expect("could not start the private cluster");
+            lifecycle::at_teardown(|| {
+                if let Some(cluster) = CLUSTER.get() {
+                    cluster.stop();
+                }
+            });
+            cluster
+        })
+    }
+
+    /// A primary and a streaming standby cloned from it, on first use. Tests
+    /// that promote the standby or stop the primary start a pair of their
+    /// own with [`Cluster::start_with_standby`].
+    pub fn shared_with_standby() -> &'static (Cluster, Cluster) {
+        static PAIR: OnceCell<(Cluster, Cluster)> = OnceCell::new();
+        PAIR.get_or_init(|| {
+            let pair = Cluster::start_with_standby().expect("could not start the private primary and standby");
+            lifecycle::at_teardown(|| {
+                if let Some((primary, standby)) = PAIR.get() {
+                    standby.stop();
+                    primary.stop();
+                }
+            });
+            pair
+        })
+    }
+
+    /// A primary and a hot standby streaming from it, stopped when dropped.
+    /// The primary is set up like [`Cluster::shared`], and the standby has
+    /// the same roles and certificates.
+    pub fn start_with_standby() -> io::Result<(Cluster, Cluster)> {
+        let primary = Cluster::start()?;
+        let standby = Cluster::new("standby")?;
+        for file in ["ca.crt", "client.crt", "client.key"] {
+            fs::copy(primary.ssl_dir().join(file), standby.ssl_dir().join(file))?;
+        }
+        let data = standby.data_dir();
+        standby.make_data_dir()?;
+        standby.run_server_tool(
+            "pg_basebackup",
+            &[
+                "-D",
+                &data.to_string_lossy(),
+                "-h",
+                &primary.data_dir().to_string_lossy(),
+                "-p",
+                &primary.port.to_string(),
+                "-U",
+                SUPERUSER,
+                "-R",
+            ],
+        )?;
+        // The copied settings are the primary's, and the last one wins.
+        let mut conf = fs::read_to_string(data.join("postgresql.conf"))?;
+        conf.push_str(&standby.settings());
+        fs::write(data.join("postgresql.conf"), conf)?;
+        standby.give_to_server(&data)?;
+        standby.pg_ctl(&["-l", &data.join("server.log").to_string_lossy(), "-w", "start"])?;
+        Ok((primary, standby))
+    }
+
+    /// A new cluster's directory with certificates, on a free port.
+    fn new(name: &str) -> io::Result<Self> {
+        lifecycle::setup();
+        let dir = tempfile::Builder::new()
+            .prefix(&format!("{}{}-{}-", DIR_PREFIX, process::id(), name))
+            .tempdir()?
+            .into_path();
+        fs::set_permissions(&dir, Permissions::from_mode(0o755))?;
+        let (bindir, run_as) = server_tools()?;
+        let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+        let cluster = Self {
+            dir,
+            bindir,
+            run_as,
+            port,
+        };
+        fs::create_dir(cluster.ssl_dir())?;
+        Ok(cluster)
+    }
+
+    fn make_data_dir(&self) -> io::Result<()> {
+        let data = self.data_dir();
+        fs::create_dir(&data)?;
+        fs::set_permissions(&data, Permissions::from_mode(0o700))?;
+        self.give_to_server(&data)
+    }
+
+    /// The settings the cluster's postgresql.conf ends with.
+    fn settings(&self) -> String {
+        format!(
+            "\nport = {}\nlisten_addresses = '127.0.0.1'\nunix_socket_directories = '{}'\n\
+             ssl = on\nssl_cert_file = 'server.crt'\nssl_key_file = 'server.key'\nssl_ca_file = 'ca.crt'\n\
+             fsync = off\n",
+            self.port,
+            self.data_dir().display()
+        )
+    }
+
+    fn start() -> io::Result<Self> {
+        let cluster = Cluster::new("cluster")?;
+        make_certificates(&cluster.ssl_dir())?;
+        let data = cluster.data_dir();
+        cluster.make_data_dir()?;
+        cluster.run_server_tool(
+            "initdb",
+            &["-D", &data.to_string_lossy(), "-U", SUPERUSER, "--auth=trust", "-E", "UTF8", "--locale=C", "-N"],
+        )?;
+
+        for file in ["server.crt", "server.key", "ca.crt"] {
+            fs::copy(cluster.ssl_dir().join(file), data.join(file))?;
+        }
+        fs::set_permissions(data.join("server.key"), Permissions::from_mode(0o600))?;
+        let mut conf = fs::read_to_string(data.join("postgresql.conf"))?;
+        conf.push_str(&cluster.settings());
+        fs::write(data.join("postgresql.conf"), conf)?;
+        let mut hba = format!(
+            "local all {0} trust\nhost all {0} 127.0.0.1/32 trust\nlocal replication {0} trust\n",
+            SUPERUSER
+        );
+        for method in Method::ALL {
+            hba.push_str(&method.hba_line());
+            hba.push('\n');
+        }
+        // Roles tests create for themselves, such as for `\password`. Other
+        // TCP connections must use TLS.
+        hba.push_str("local all all scram-sha-256\nhostssl all all 127.0.0.1/32 scram-sha-256\n");
+        fs::write(data.join("pg_hba.conf"), hba)?;
+        fs::write(data.join("pg_ident.conf"), format!("tester {} {}\n", os_user()?, Method::Peer.role()))?;
+        cluster.give_to_server(&data)?;
+        cluster.pg_ctl(&["-l", &data.join("server.log").to_string_lossy(), "-w", "start"])?;
+
+        let mut sql = String::from("GRANT CREATE ON SCHEMA public TO PUBLIC;\n");
+        for method in Method::ALL {
+            let encryption = if method == Method::Md5 { "md5" } else { "scram-sha-256" };
+            let password = if method.needs_password() { format!(" PASSWORD '{}'", PASSWORD) } else { String::new() };
+            sql.push_str(&format!(
+                "SET password_encryption = '{}';\nCREATE ROLE {} LOGIN{};\n",
+                encryption,
+                method.role(),
+                password
+            ));
+        }
+        let output = cluster.query(&sql)?;
+        if !output.status.success() {
+            return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).into_owned()));
+        }
+        Ok(cluster)
+    }
+
+    fn stop(&self) {
+        let _ = self.crash();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+
+    /// Stops the server at once, the way a crash would, leaving its data.
+    pub fn crash(&self) -> io::Result<()> {
+        self.pg_ctl(&["-m", "immediate", "-w", "stop"])
+    }
+
+    /// Promotes a standby, waiting until it accepts writes.
+    pub fn promote(&self) -> io::Result<()> {
+        self.pg_ctl(&["-w", "promote"])
+    }
+
+    /// Waits until `standby` has replayed everything written here so far.
+    pub fn wait_for_replay(&self, standby: &Cluster) -> io::Result<()> {
+        let output = self.psql(SUPERUSER).args(["-XAt", "-c", "SELECT pg_current_wal_lsn();"]).output()?;
+        check(output.clone())?;
+        let lsn = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let sql = format!("SELECT pg_last_wal_replay_lsn() >= '{}';", lsn);
+        let deadline = Instant::now() + REPLAY_TIMEOUT;
+        loop {
+            let output = standby.psql(SUPERUSER).args(["-XAt", "-c", &sql]).output()?;
+            if output.stdout == b"t\n" {
+                return Ok(());
+            }
+            if Instant::now() > deadline {
+                return Err(io::Error::other(format!("the standby didn't replay up to {} in time", lsn)));
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    fn pg_ctl(&self, args: &[&str]) -> io::Result<()> {
+        let data = self.data_dir();
+        let mut all = vec!["-D", data.to_str().unwrap()];
+        all.extend(args);
+        self.run_server_tool("pg_ctl", &all)
+    }
+
+    pub fn data_dir(&self) -> PathBuf {
+        self.dir.join("data")
+    }
+
+    /// The CA, server and client certificates and keys, named `ca.crt`,
+    /// `server.crt`, `server.key`, `client.crt` and `client.key`. The client
+    /// certificate is for the cert method's role.
+    pub fn ssl_dir(&self) -> PathBuf {
+        self.dir.join("ssl")
+    }
+
+    /// psql connecting as `role` over the Unix socket, with no password from
+    /// the environment or a password file. Non-interactive runs get the same
+    /// settings as PTY sessions.
+    pub fn psql(&self, role: &str) -> Command {
+        self.client(&self.data_dir().to_string_lossy(), role)
+    }
+
+    fn client(&self, host: &str, role: &str) -> Command {
+        isolation::use_own_server();
+        let mut command = psql_command();
+        command
+            .args(["-h", host, "-p", &self.port.to_string()])
+            .args(["-U", role, "-d", DATABASE])
+            .env_remove("PGPASSWORD")
+            .env("PGPASSFILE", self.dir.join("no_password_file"));
+        command
+    }
+
+    /// psql with none of the environment's connection settings and no
+    /// password or service file, to connect with only what the caller adds:
+    /// a `-d` URI or key=value string, options or variables. It doesn't ask
+    /// for TLS.
+    pub fn psql_defaults(&self) -> Command {
+        isolation::use_own_server();
+        let mut command = psql_command();
+        for name in ["PGHOST", "PGHOSTADDR", "PGPORT", "PGUSER", "PGDATABASE", "PGSERVICE", "PGPASSWORD"] {
+            command.env_remove(name);
+        }
+        command
+            .env("PGPASSFILE", self.dir.join("no_password_file"))
+            .env("PGSERVICEFILE", self.dir.join("no_service_file"))
+            .env("PGSSLMODE", "disable");
+        command
+    }
+
+    /// psql connecting as `role` over TCP with `sslmode`, with the cluster's CA
+    /// as the root certificate and no client certificate.
+    pub fn psql_tcp(&self, role: &str, sslmode: &str) -> Command {
+        let mut command = self.client("127.0.0.1", role);
+        let missing = self.dir.join("no_certificate");
+        command
+            .env("PGSSLMODE", sslmode)
+            .env("PGSSLROOTCERT", self.ssl_dir().join("ca.crt"))
+            .env("PGSSLCERT", &missing)
+            .env("PGSSLKEY", &missing);
+        command
+    }
+
+    /// psql connecting as `role` over TCP with TLS, checking the server's
+    /// certificate against the cluster's CA and presenting the client
+    /// certificate if `client_certificate`.
+    pub fn psql_tls(&self, role: &str, client_certificate: bool) -> Command {
+        let mut command = self.psql_tcp(role, "verify-full");
+        if client_certificate {
+            let ssl = self.ssl_dir();
+            command.env("PGSSLCERT", ssl.join("client.crt")).env("PGSSLKEY", ssl.join("client.key"));
+        }
+        command
+    }
+
+    /// Runs `sql` as the superuser.
+    pub fn query(&self, sql: &str) -> io::Result<Output> {
+        self.psql(SUPERUSER).args(["-v", "ON_ERROR_STOP=1", "-q", "-c", sql]).output()
+    }
+
+    fn give_to_server(&self, path: &Path) -> io::Result<()> {
+        let Some(user) = &self.run_as else {
+            return Ok(());
+        };
+        check(Command::new("chown").arg("-R").arg(user).arg(path).output()?)
+    }
+
+    fn run_server_tool(&self, tool: &str, args: &[&str]) -> io::Result<()> {
+        let program = self.bindir.join(tool);
+        let mut command = match &self.run_as {
+            Some(user) => {
+                let mut command = Command::new("runuser");
+                command.args(["-u", user, "--"]).arg(program);
+                command
+            }
+            None => Command::new(program),
+        };
+        check(command.args(args).current_dir(&self.dir).output()?)
+    }
+}
+
+/// Where the server tools are and who they run as.
+fn server_tools() -> io::Result<(PathBuf, Option<String>)> {
+    let output = Command::new("pg_config").arg("--bindir").output()?;
+    let bindir = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    // SAFETY: geteuid has no preconditions.
+    let run_as = (unsafe { geteuid() } == 0)
+        .then(|| env::var("PSQL_TEST_CLUSTER_USER").unwrap_or_else(|_| "postgres".to_string()));
+    Ok((bindir, run_as))
+}
+
+/// Stops the server of a cluster in `dir` that a killed run left behind, if
+/// it still runs; the caller removes the directory. pg_ctl signals whatever process
+/// `postmaster.pid` names, which after a reboot or pid wraparound may be
+/// anything, even another cluster's backend; so it only runs if that process
+/// still has the data directory as its working directory, as a postmaster
+/// does.
+pub fn stop_abandoned(dir: &Path) {
+    let data = dir.join("data");
+    let Ok(pid_file) = fs::read_to_string(data.join("postmaster.pid")) else {
+        return;
+    };
+    let Some(pid) = pid_file.lines().next().and_then(|pid| pid.trim().parse::<u32>().ok()) else {
+        return;
+    };
+    match (fs::read_link(format!("/proc/{}/cwd", pid)), data.canonicalize()) {
+        (Ok(cwd), Ok(data)) if cwd == data => {}
+        _ => return,
+    }
+    let Ok((bindir, run_as)) = server_tools() else {
+        return;
+    };
+    drop(Cluster {
+        dir: dir.to_path_buf(),
+        bindir,
+        run_as,
+        port: 0,
+    });
+}
+
+fn check(output: Output) -> io::Result<()> {
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        )))
+    }
+}
+
+fn os_user() -> io::Result<String> {
+    let output = Command::new("id").arg("-un").output()?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// A CA, a server certificate for localhost and 127.0.0.1 and a client
+/// certificate for the cert method's role, all signed by the CA.
+fn make_certificates(dir: &Path) -> io::Result<()> {
+    let openssl = |args: &[&str]| -> io::Result<()> { check(Command::new("openssl").args(args).current_dir(dir).output()?) };
+    openssl(&["req", "-new", "-x509", "-nodes", "-days", "2", "-subj", "/CN=psql_tester CA", "-keyout", "ca.key", "-out", "ca.crt"])?;
+    fs::write(dir.join("server.ext"), "subjectAltName = DNS:localhost, IP:127.0.0.1\n")?;
+    for (name, subject) in [("server", "/CN=localhost".to_string()), ("client", format!("/CN={}", Method::Cert.role()))] {
+        let (key, csr, crt) = (format!("{}.key", name), format!("{}.csr", name), format!("{}.crt", name));
+        openssl(&["req", "-new", "-nodes", "-subj", &subject, "-keyout", &key, "-out", &csr])?;
+        let mut args = vec!["x509", "-req", "-in", &csr, "-CA", "ca.crt", "-CAkey", "ca.key", "-CAcreateserial", "-days", "2", "-out", &crt];
+        if name == "server" {
+            args.extend(["-extfile", "server.ext"]);
+        }
+        openssl(&args)?;
+        fs::set_permissions(dir.join(&key), Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
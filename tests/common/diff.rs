@@ -0,0 +1,246 @@
+//! The diff `verify!` prints. Changed lines are paired up and the words or
+//! characters that differ are emphasized, with spaces, tabs, line ends and
+//! control characters drawn visibly so that a trailing blank or a tab in
+//! place of a space stands out. Output that isn't text gets a hex dump diff.
+
+use crate::common::pgcopy;
+use similar::{ChangeTag, DiffOp, TextDiff};
+use std::io::Write;
+use termcolor::{Buffer, Color, ColorSpec, WriteColor};
+
+/// The diff from `actual` to `expected`, rendered twice.
+pub struct Diff {
+    /// Plain text for reports, with emphasized segments marked `[-…-]` and
+    /// `{+…+}`.
+    pub plain: String,
+    /// ANSI colors for the terminal, with emphasized segments underlined.
+    pub colored: String,
+}
+
+/// Renders the diff from `actual` to `expected`. Nothing is printed, so
+/// callers decide when it is worth showing.
+pub fn render_diff(actual: &[u8], expected: &[u8]) -> Diff {
+    let mut printer = Printer::new();
+    match (std::str::from_utf8(actual), std::str::from_utf8(expected)) {
+        (Ok(actual), Ok(expected)) if !is_binary(actual) && !is_binary(expected) => {
+            line_diff(&mut printer, actual, expected, visible)
+        }
+        _ => {
+            if pgcopy::is_pgcopy(actual) && pgcopy::is_pgcopy(expected) {
+                pgcopy_diff(&mut printer, actual, expected);
+            }
+            printer.line(" ", "Binary output, shown as a hex dump:\n");
+            line_diff(&mut printer, &hex_dump(actual), &hex_dump(expected), str::to_string)
+        }
+    }
+    Diff {
+        plain: printer.plain,
+        colored: String::from_utf8_lossy(&printer.colored.into_inner()).into_owned(),
+    }
+}
+
+/// The structural differences between two binary COPY files, or what keeps
+/// either from parsing.
+fn pgcopy_diff(printer: &mut Printer, actual: &[u8], expected: &[u8]) {
+    printer.line(" ", "Binary COPY structure, actual vs expected:\n");
+    match (pgcopy::parse(actual), pgcopy::parse(expected)) {
+        (Ok(actual), Ok(expected)) => {
+            for difference in pgcopy::differences(&actual, &expected) {
+                printer.line("!", &format!("{}\n", difference));
+            }
+        }
+        (actual, expected) => {
+            if let Err(err) = actual {
+                printer.line("-", &format!("invalid: {}\n", err));
+            }
+            if let Err(err) = expected {
+                printer.line("+", &format!("invalid: {}\n", err));
+            }
+        }
+    }
+}
+
+/// Text with NUL bytes or other controls that aren't tabs, line ends or
+/// escape sequences is better compared byte by byte.
+fn is_binary(text: &str) -> bool {
+    text.chars().any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x1b'))
+}
+
+/// Diffs by line, then pairs up changed lines to emphasize what changed in
+/// each, with `show` drawing changed text.
+fn line_diff(printer: &mut Printer, actual: &str, expected: &str, show: fn(&str) -> String) {
+    let diff = TextDiff::from_lines(actual, expected);
+    for op in diff.ops() {
+        match *op {
+            DiffOp::Equal { old_index, len, .. } => {
+                for line in &diff.old_slices()[old_index..old_index + len] {
+                    printer.line(" ", line);
+                }
+            }
+            DiffOp::Delete { old_index, old_len, .. } => {
+                for line in &diff.old_slices()[old_index..old_index + old_len] {
+                    printer.line("-", &show(line));
+                }
+            }
+            DiffOp::Insert { new_index, new_len, .. } => {
+                for line in &diff.new_slices()[new_index..new_index + new_len] {
+                    printer.line("+", &show(line));
+                }
+            }
+            DiffOp::Replace { old_index, old_len, new_index, new_len } => {
+                let old = &diff.old_slices()[old_index..old_index + old_len];
+                let new = &diff.new_slices()[new_index..new_index + new_len];
+                // Lines are compared pairwise; the rest of the longer side is
+                // a plain deletion or insertion.
+                let pairs = old_len.min(new_len);
+                let mut inserts = Vec::new();
+                for i in 0..pairs {
+                    let (deleted, inserted) = inline(old[i], new[i], show);
+                    printer.segments("-", &deleted);
+                    inserts.push(inserted);
+                }
+                for line in &old[pairs..] {
+                    printer.line("-", &show(line));
+                }
+                for inserted in &inserts {
+                    printer.segments("+", inserted);
+                }
+                for line in &new[pairs..] {
+                    printer.line("+", &show(line));
+                }
+            }
+        }
+    }
+}
+
+/// A line split into segments, each drawn visibly and marked if it differs.
+type Segments = Vec<(bool, String)>;
+
+/// The segments of `old` and `new` that differ: by character when the lines
+/// are mostly alike, otherwise by word.
+fn inline(old: &str, new: &str, show: fn(&str) -> String) -> (Segments, Segments) {
+    let chars = TextDiff::from_chars(old, new);
+    let words;
+    let diff = if chars.ratio() >= 0.5 {
+        &chars
+    } else {
+        words = TextDiff::from_words(old, new);
+        &words
+    };
+    let mut deleted: Segments = Vec::new();
+    let mut inserted: Segments = Vec::new();
+    for change in diff.iter_all_changes() {
+        let text = show(change.value());
+        let (side, emphasized): (&mut Segments, bool) = match change.tag() {
+            ChangeTag::Equal => {
+                push_segment(&mut deleted, false, &text);
+                (&mut inserted, false)
+            }
+            ChangeTag::Delete => (&mut deleted, true),
+            ChangeTag::Insert => (&mut inserted, true),
+        };
+        push_segment(side, emphasized, &text);
+    }
+    (deleted, inserted)
+}
+
+fn push_segment(segments: &mut Segments, emphasized: bool, text: &str) {
+    match segments.last_mut() {
+        Some((last, last_text)) if *last == emphasized => last_text.push_str(text),
+        _ => segments.push((emphasized, text.to_string())),
+    }
+}
+
+/// `text` with whitespace and control characters drawn as symbols: `·` for a
+/// space, `→` for a tab, `␍` for a carriage return and `⏎` before the line end.
+fn visible(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            ' ' => out.push('·'),
+            '\t' => out.push('→'),
+            '\r' => out.push('␍'),
+            '\n' => out.push_str("⏎\n"),
+            '\x1b' => out.push('␛'),
+            c if c.is_control() => out.push_str(&format!("\\x{:02x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// 16 bytes per line as offset, hex and printable characters.
+fn hex_dump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect();
+        out.push_str(&format!("{:08x}  {:<47}  |{}|\n", i * 16, hex.join(" "), ascii));
+    }
+    out
+}
+
+/// Writes the diff in color and as plain text side by side.
+struct Printer {
+    colored: Buffer,
+    plain: String,
+}
+
+impl Printer {
+    fn new() -> Self {
+        Self {
+            colored: Buffer::ansi(),
+            plain: String::new(),
+        }
+    }
+
+    fn line(&mut self, sign: &str, text: &str) {
+        self.segments(sign, &[(false, text.to_string())]);
+    }
+
+    fn segments(&mut self, sign: &str, segments: &[(bool, String)]) {
+        let color = match sign {
+            "-" => Some(Color::Red),
+            "+" => Some(Color::Green),
+            "!" => Some(Color::Yellow),
+            _ => None,
+        };
+        let (open, close) = if sign == "-" { ("[-", "-]") } else { ("{+", "+}") };
+        let mut normal = ColorSpec::new();
+        normal.set_fg(color);
+        let mut emphasized = normal.clone();
+        emphasized.set_bold(true).set_underline(true);
+
+        self.colored.set_color(&normal).unwrap();
+        let _ = self.colored.write_all(sign.as_bytes());
+        self.plain.push_str(sign);
+        for (is_emphasized, text) in segments {
+            // The line break itself stays outside the emphasis.
+            let (text, end) = match text.strip_suffix('\n') {
+                Some(text) => (text, "\n"),
+                None => (text.as_str(), ""),
+            };
+            let spec = if *is_emphasized { &emphasized } else { &normal };
+            self.colored.set_color(spec).unwrap();
+            let _ = self.colored.write_all(text.as_bytes());
+            if *is_emphasized {
+                self.plain.push_str(open);
+                self.plain.push_str(text);
+                self.plain.push_str(close);
+            } else {
+                self.plain.push_str(text);
+            }
+            self.colored.set_color(&normal).unwrap();
+            let _ = self.colored.write_all(end.as_bytes());
+            self.plain.push_str(end);
+        }
+        self.colored.reset().unwrap();
+        if !self.plain.ends_with('\n') {
+            let _ = self.colored.write_all(b"\n");
+            self.plain.push('\n');
+        }
+    }
+}
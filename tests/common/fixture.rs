@@ -0,0 +1,56 @@
+//! COPY data in text and CSV format, escaped the way the server writes it,
+//! so tests can build inputs without a round trip through the server. Binary
+//! data is written by [`crate::common::pgcopy::PgCopy`].
+
+/// One field in text format: `\N` for NULL, and backslash, the delimiter
+/// tab and line ends escaped with a backslash.
+pub fn text_field(value: Option<&str>) -> String {
+    let Some(value) = value else {
+        return "\\N".to_string();
+    };
+    let mut out = String::new();
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\x08' => out.push_str("\\b"),
+            '\x0b' => out.push_str("\\v"),
+            '\x0c' => out.push_str("\\f"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// One field in CSV format: nothing for NULL, and quoted if it is empty or
+/// contains a comma, quote or line end.
+pub fn csv_field(value: Option<&str>) -> String {
+    let Some(value) = value else {
+        return String::new();
+    };
+    if value.is_empty() || value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Rows in text format, one line each.
+pub fn text(rows: &[&[Option<&str>]]) -> String {
+    rows.iter()
+        .map(|row| format!("{}\n", row.iter().map(|&value| text_field(value)).collect::<Vec<_>>().join("\t")))
+        .collect()
+}
+
+/// Rows in CSV format, one line each unless a quoted field has line ends. A
+/// lone `\.` field is quoted too, as it would otherwise end the data.
+pub fn csv(rows: &[&[Option<&str>]]) -> String {
+    rows.iter()
+        .map(|row| match row {
+            [Some("\\.")] => "\"\\.\"\n".to_string(),
+            row => format!("{}\n", row.iter().map(|&value| csv_field(value)).collect::<Vec<_>>().join(",")),
+        })
+        .collect()
+}
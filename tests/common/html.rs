@@ -0,0 +1,176 @@
+//! A self-contained HTML page of the matrix, for reviewing a psql patch: one
+//! row per method and source, one column per format and psql build, and
+//! each cell expanding to its diff and session transcript.
+//!
+//! Every run stores its cells, already rendered, in `versions/<label>.tsv` in
+//! the report directory, and the page shows all builds found there. The
+//! label is `PSQL_TEST_VERSION_LABEL`, or the `psql --version` output if that
+//! is unset, which doesn't tell a patched build from the unpatched one it
+//! was made from. Run the suite once per psql build with the same
+//! `PSQL_TEST_REPORT_DIR` and its own label to compare them side by side.
+
+use crate::common::report::{psql_version, xml_escape, CellResult};
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The rows and columns of the README's matrix, in its order.
+const ROWS: [&str; 6] = [
+    "command file",
+    "script stdin",
+    "terminal tty",
+    "terminal stdin",
+    "terminal -n tty",
+    "terminal -n stdin",
+];
+const FORMATS: [&str; 3] = ["text", "csv", "binary"];
+
+/// One cell of one build, as stored in `versions/*.tsv`.
+struct Entry {
+    row: String,
+    format: String,
+    status: String,
+    html: String,
+}
+
+/// Stores this run's cells and rewrites `results.html` from every version.
+pub fn write(dir: &Path, results: &[CellResult]) -> io::Result<()> {
+    let versions_dir = dir.join("versions");
+    fs::create_dir_all(&versions_dir)?;
+    let label = env::var("PSQL_TEST_VERSION_LABEL").unwrap_or_else(|_| psql_version().to_string());
+    let slug: String = label
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' { c } else { '_' })
+        .collect();
+    let mut tsv = format!("{}\n", label.replace(['\n', '\t'], " "));
+    for result in results {
+        writeln!(
+            tsv,
+            "{}\t{}\t{}\t{}",
+            result.cell.row(),
+            result.cell.format,
+            result.status.name(),
+            cell_html(result).replace('\n', "&#10;").replace('\t', "&#9;")
+        )
+        .unwrap();
+    }
+    fs::write(versions_dir.join(format!("{}.tsv", slug)), tsv)?;
+
+    let mut versions = Vec::new();
+    let mut paths: Vec<_> = fs::read_dir(&versions_dir)?.map(|entry| entry.map(|entry| entry.path())).collect::<Result<_, _>>()?;
+    paths.sort();
+    for path in paths.iter().filter(|path| path.extension().is_some_and(|extension| extension == "tsv")) {
+        let contents = fs::read_to_string(path)?;
+        let mut lines = contents.lines();
+        let version = lines.next().unwrap_or_default().to_string();
+        let entries: Vec<Entry> = lines
+            .filter_map(|line| {
+                let mut fields = line.splitn(4, '\t');
+                Some(Entry {
+                    row: fields.next()?.to_string(),
+                    format: fields.next()?.to_string(),
+                    status: fields.next()?.to_string(),
+                    html: fields.next()?.to_string(),
+                })
+            })
+            .collect();
+        versions.push((version, entries));
+    }
+    fs::write(dir.join("results.html"), page(&versions))
+}
+
+fn page(versions: &[(String, Vec<Entry>)]) -> String {
+    let mut out = String::from(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>psql \copy matrix</title>
+<style>
+body { font-family: sans-serif; margin: 1em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #999; padding: 4px 8px; vertical-align: top; text-align: left; }
+td.passed { background: #d4f4d4; }
+td.flaky { background: #fbeeb8; }
+td.failed { background: #f8d0d0; }
+td.missing { background: #eee; color: #777; }
+summary { cursor: pointer; }
+pre { background: #fff; border: 1px solid #ccc; padding: 4px; max-width: 90ch; overflow-x: auto; }
+.delete { color: #b00; }
+.insert { color: #070; }
+</style>
+</head>
+<body>
+<h1>psql <code>\copy</code> matrix</h1>
+<table>
+"#,
+    );
+    writeln!(out, "<tr><th rowspan=\"2\">method / source</th>").unwrap();
+    for format in FORMATS {
+        writeln!(out, "<th colspan=\"{}\">{}</th>", versions.len(), format).unwrap();
+    }
+    writeln!(out, "</tr>\n<tr>").unwrap();
+    for _ in FORMATS {
+        for (version, _) in versions {
+            writeln!(out, "<th>{}</th>", xml_escape(version)).unwrap();
+        }
+    }
+    writeln!(out, "</tr>").unwrap();
+
+    let mut rows: Vec<&str> = ROWS.to_vec();
+    for (_, entries) in versions {
+        for entry in entries {
+            if !rows.contains(&entry.row.as_str()) {
+                rows.push(&entry.row);
+            }
+        }
+    }
+    for row in rows {
+        writeln!(out, "<tr><th>{}</th>", xml_escape(row)).unwrap();
+        for format in FORMATS {
+            for (_, entries) in versions {
+                match entries.iter().find(|entry| entry.row == row && entry.format == format) {
+                    Some(entry) => writeln!(out, "<td class=\"{}\">{}</td>", xml_escape(&entry.status), entry.html).unwrap(),
+                    None => writeln!(out, "<td class=\"missing\">not run</td>").unwrap(),
+                }
+            }
+        }
+        writeln!(out, "</tr>").unwrap();
+    }
+    out.push_str("</table>\n</body>\n</html>\n");
+    out
+}
+
+/// The contents of a cell: its status and time, expanding to the test name,
+/// the failure message and colored diff if there are any, and the session
+/// transcript of a terminal cell.
+fn cell_html(result: &CellResult) -> String {
+    let mut summary = format!("{} {:.2}s", result.status.name(), result.duration.as_secs_f64());
+    if result.attempts > 1 {
+        write!(summary, ", {} attempts", result.attempts).unwrap();
+    }
+    let mut out = format!("<details><summary>{}</summary>\n", summary);
+    writeln!(out, "<p><code>{}</code></p>", xml_escape(&result.test)).unwrap();
+    if let Some(message) = &result.message {
+        writeln!(out, "<p>{}</p>", xml_escape(message)).unwrap();
+    }
+    if let Some(diff) = &result.diff {
+        out.push_str("<pre>");
+        for line in diff.lines() {
+            let class = match line.chars().next() {
+                Some('-') => "delete",
+                Some('+') => "insert",
+                _ => "",
+            };
+            writeln!(out, "<span class=\"{}\">{}</span>", class, xml_escape(line)).unwrap();
+        }
+        out.push_str("</pre>\n");
+    }
+    if let Some(log) = &result.session_log {
+        writeln!(out, "<pre>{}</pre>", xml_escape(log)).unwrap();
+    }
+    out.push_str("</details>");
+    out
+}
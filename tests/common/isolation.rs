@@ -0,0 +1,211 @@
+//! What keeps tests apart when `cargo test` runs them in parallel against one
+//! server. libtest runs each test on its own thread, so per-test state lives
+//! in a thread local:
+//!
+//! - a schema, created the first time the test runs psql, which every later
+//!   psql of the test gets as its `search_path` through `PGOPTIONS`;
+//! - a directory of its own under the shared fixtures, from [`test_dir`].
+//!
+//! Both are named `psql_test_<pid>_<n>` and removed when the test's thread
+//! exits, unless the test failed and artifacts are kept; see
+//! [`lifecycle`](super::lifecycle). Tests against the mock
+//! backend don't get a schema, since they may have no server to create it
+//! on, nor do tests against the private cluster. Mock backends, proxies and
+//! the cluster listen on sockets and ports of their own.
+//!
+//! [`limit`] caps how many tests use a resource at once, for example PTY
+//! sessions on a loaded machine.
+
+use crate::common::get_test_environment;
+use crate::common::lifecycle::{self, SCHEMA_PREFIX};
+use once_cell::sync::Lazy;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+/// What belongs to the test running on this thread.
+#[derive(Default)]
+struct Isolation {
+    name: Option<String>,
+    schema: Option<String>,
+    dir: Option<PathBuf>,
+    mock: bool,
+    failed: bool,
+}
+
+impl Isolation {
+    /// A name unique to this test and process, such as `psql_test_1234_7`.
+    fn name(&mut self) -> String {
+        static NEXT: AtomicUsize = AtomicUsize::new(1);
+        self.name
+            .get_or_insert_with(|| format!("{}{}_{}", SCHEMA_PREFIX, process::id(), NEXT.fetch_add(1, Ordering::SeqCst)))
+            .clone()
+    }
+
+    fn dir(&mut self) -> PathBuf {
+        if let Some(dir) = &self.dir {
+            return dir.clone();
+        }
+        let dir = get_test_environment().temp_dir.join(self.name());
+        fs::create_dir(&dir).unwrap();
+        self.dir = Some(dir.clone());
+        dir
+    }
+}
+
+impl Drop for Isolation {
+    fn drop(&mut self) {
+        if self.failed && lifecycle::keep_artifacts() {
+            // The directory says which schema holds the test's tables.
+            if let Some(schema) = &self.schema {
+                let dir = get_test_environment().temp_dir.join(schema);
+                let _ = fs::create_dir_all(&dir).and_then(|_| fs::write(dir.join("schema"), schema));
+            }
+            return;
+        }
+        if let Some(schema) = &self.schema {
+            let _ = Command::new("psql")
+                .args(["-X", "-q", "-c", &format!("DROP SCHEMA IF EXISTS {} CASCADE;", schema)])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+        }
+        if let Some(dir) = &self.dir {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+thread_local! {
+    static ISOLATION: RefCell<Isolation> = RefCell::new(Isolation::default());
+}
+
+/// Marks the calling test as running against a server of its own, the mock
+/// backend or the private cluster, rather than the shared test server.
+pub fn use_own_server() {
+    ISOLATION.with(|isolation| isolation.borrow_mut().mock = true);
+}
+
+/// The calling test's schema, created on first use.
+pub fn schema() -> String {
+    ISOLATION.with(|isolation| {
+        let mut isolation = isolation.borrow_mut();
+        if let Some(schema) = &isolation.schema {
+            return schema.clone();
+        }
+        lifecycle::setup();
+        lifecycle::collect_schemas();
+        let schema = isolation.name();
+        let output = Command::new("psql")
+            .args(["-X", "-q", "-c", &format!("CREATE SCHEMA {};", schema)])
+            .output()
+            .expect("could not run psql");
+        assert!(
+            output.status.success(),
+            "could not create schema {}: {}",
+            schema,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        isolation.schema = Some(schema.clone());
+        schema
+    })
+}
+
+/// `PGOPTIONS` with the calling test's schema as the search path.
+pub fn pgoptions() -> String {
+    let options = env::var("PGOPTIONS").unwrap_or_default();
+    format!("{} -c search_path={}", options, schema()).trim_start().to_string()
+}
+
+/// Points `command` at the calling test's schema, unless the test uses a
+/// server of its own.
+pub fn isolate(command: &mut Command) {
+    if !ISOLATION.with(|isolation| isolation.borrow().mock) {
+        command.env("PGOPTIONS", pgoptions());
+    }
+}
+
+/// A directory for the calling test's own files, created on first use.
+pub fn test_dir() -> PathBuf {
+    ISOLATION.with(|isolation| isolation.borrow_mut().dir())
+}
+
+/// Marks the calling test as failed. With artifacts kept, it keeps its
+/// directory and schema.
+pub fn mark_failed() {
+    // From the panic hook, the thread may be exiting or in the middle of
+    // setting up its isolation.
+    let _ = ISOLATION.try_with(|isolation| {
+        let Ok(mut isolation) = isolation.try_borrow_mut() else {
+            return;
+        };
+        if !isolation.failed {
+            isolation.failed = true;
+            lifecycle::record_failure();
+        }
+    });
+}
+
+/// Saves `contents` as `name` in the calling test's directory if artifacts
+/// are kept, and marks the test as failed.
+pub fn keep_artifact(name: &str, contents: &str) {
+    mark_failed();
+    if lifecycle::keep_artifacts() {
+        let _ = fs::write(test_dir().join(name), contents);
+    }
+}
+
+struct Semaphore {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+/// A hold on one unit of a resource, given back when dropped.
+pub struct Permit(Option<Arc<Semaphore>>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(semaphore) = &self.0 {
+            *semaphore.available.lock().unwrap() += 1;
+            semaphore.released.notify_one();
+        }
+    }
+}
+
+/// Waits until fewer than `PSQL_TEST_LIMIT_<RESOURCE>` tests hold `resource`
+/// and takes it. Without the variable there is no limit. The harness takes
+/// `psql` for each non-interactive psql run and `pty` for each PTY session.
+pub fn limit(resource: &str) -> Permit {
+    static SEMAPHORES: Lazy<Mutex<HashMap<String, Option<Arc<Semaphore>>>>> = Lazy::new(Default::default);
+    let semaphore = SEMAPHORES
+        .lock()
+        .unwrap()
+        .entry(resource.to_string())
+        .or_insert_with(|| {
+            let name = format!("PSQL_TEST_LIMIT_{}", resource.to_uppercase());
+            let value = env::var(&name).ok()?;
+            let limit: usize = value
+                .parse()
+                .ok()
+                .filter(|&limit| limit > 0)
+                .unwrap_or_else(|| panic!("{} must be a positive count, not {:?}", name, value));
+            Some(Arc::new(Semaphore {
+                available: Mutex::new(limit),
+                released: Condvar::new(),
+            }))
+        })
+        .clone();
+    if let Some(semaphore) = &semaphore {
+        let mut available = semaphore.available.lock().unwrap();
+        while *available == 0 {
+            available = semaphore.released.wait(available).unwrap();
+        }
+        *available -= 1;
+    }
+    Permit(semaphore)
+}
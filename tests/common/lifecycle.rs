@@ -0,0 +1,164 @@
+//! Setup and teardown of the whole test process. libtest has no hooks around
+//! a run, and the shared [`TestEnvironment`](super::TestEnvironment) is a
+//! static, which is never dropped. So [`setup`], run on first use of the
+//! environment or of a test schema, registers [`teardown`] with the C
+//! library's `atexit`, and teardown removes the fixtures directory.
+//!
+//! Runs that were killed never get there, so setup also collects what
+//! earlier runs left behind: fixture directories and test schemas of
+//! processes that are no longer running, and any private cluster they left
+//! running.
+//!
+//! With `PSQL_TEST_KEEP_ARTIFACTS` set, a failing test keeps its directory
+//! and schema, and its directory gets the test's diff and session log. If
+//! any test failed, the fixtures directory stays too, and later runs leave
+//! it and its schemas alone until it is removed by hand.
+
+use crate::common::cluster;
+use crate::common::isolation;
+use std::env;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, Once};
+
+/// What fixture directories are named, followed by the process id and `-`.
+pub const DIR_PREFIX: &str = "psql_tester-";
+
+/// What test schemas are named, followed by the process id, `_` and a count.
+pub const SCHEMA_PREFIX: &str = "psql_test_";
+
+/// The file marking a fixtures directory that was kept on purpose.
+const KEPT: &str = "KEPT";
+
+/// `ESRCH`, the `kill` error for a process that doesn't exist.
+const NO_SUCH_PROCESS: i32 = 3;
+
+extern "C" {
+    fn atexit(callback: extern "C" fn()) -> i32;
+    fn kill(pid: i32, signal: i32) -> i32;
+}
+
+static FAILURES: AtomicUsize = AtomicUsize::new(0);
+
+static TEARDOWN: Mutex<Vec<fn()>> = Mutex::new(Vec::new());
+
+/// Runs `hook` at teardown, before the fixtures are removed.
+pub fn at_teardown(hook: fn()) {
+    TEARDOWN.lock().unwrap().push(hook);
+}
+
+pub fn keep_artifacts() -> bool {
+    env::var_os("PSQL_TEST_KEEP_ARTIFACTS").is_some()
+}
+
+/// Counts a failed test, whose artifacts may be kept.
+pub fn record_failure() {
+    FAILURES.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Registers teardown and a panic hook marking the panicking test as failed,
+/// and removes the fixture directories of earlier runs. Runs once.
+pub fn setup() {
+    static SETUP: Once = Once::new();
+    SETUP.call_once(|| {
+        // SAFETY: `teardown_at_exit` is a plain function that doesn't unwind.
+        unsafe { atexit(teardown_at_exit) };
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            isolation::mark_failed();
+            previous(info);
+        }));
+        collect_directories();
+    });
+}
+
+extern "C" fn teardown_at_exit() {
+    let _ = panic::catch_unwind(AssertUnwindSafe(teardown));
+}
+
+/// Removes the fixtures directory, unless artifacts are kept and a test
+/// failed.
+pub fn teardown() {
+    for hook in TEARDOWN.lock().unwrap().drain(..) {
+        hook();
+    }
+    let Some(environment) = super::TEST_ENVIRONMENT.get() else {
+        return;
+    };
+    if keep_artifacts() && FAILURES.load(Ordering::SeqCst) > 0 {
+        let _ = fs::write(environment.temp_dir.join(KEPT), "");
+        eprintln!("Kept the artifacts of failing tests in {}", environment.temp_dir.display());
+    } else {
+        environment.cleanup();
+    }
+}
+
+fn is_running(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks whether the process exists.
+    let found = unsafe { kill(pid as i32, 0) } == 0;
+    found || io::Error::last_os_error().raw_os_error() != Some(NO_SUCH_PROCESS)
+}
+
+/// The fixture directories in the temporary directory, with the process id
+/// each belongs to.
+fn fixture_directories() -> Vec<(u32, PathBuf)> {
+    let Ok(entries) = fs::read_dir(env::temp_dir()) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?;
+            let pid = name.strip_prefix(DIR_PREFIX)?.split('-').next()?.parse().ok()?;
+            Some((pid, path))
+        })
+        .collect()
+}
+
+/// Whether the process left behind artifacts to keep, or is still running.
+fn is_in_use(pid: u32) -> bool {
+    pid == process::id()
+        || is_running(pid)
+        || fixture_directories().iter().any(|(owner, path)| *owner == pid && path.join(KEPT).exists())
+}
+
+fn collect_directories() {
+    for (pid, path) in fixture_directories() {
+        if !is_in_use(pid) {
+            // A private cluster outlives the run that started it.
+            cluster::stop_abandoned(&path);
+            let _ = fs::remove_dir_all(path);
+        }
+    }
+}
+
+/// Drops the test schemas of earlier runs. Runs once, before the first test
+/// schema is created.
+pub fn collect_schemas() {
+    static COLLECT: Once = Once::new();
+    COLLECT.call_once(|| {
+        let Ok(output) = Command::new("psql")
+            .args(["-XAt", "-c", &format!("SELECT nspname FROM pg_namespace WHERE starts_with(nspname, '{}');", SCHEMA_PREFIX)])
+            .output()
+        else {
+            return;
+        };
+        let stale: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|schema| {
+                let pid = schema.strip_prefix(SCHEMA_PREFIX).and_then(|rest| rest.split('_').next()?.parse().ok());
+                pid.is_some_and(|pid| !is_in_use(pid))
+            })
+            .map(|schema| format!("\"{}\"", schema.replace('"', "\"\"")))
+            .collect();
+        if !stale.is_empty() {
+            let _ = Command::new("psql")
+                .args(["-X", "-q", "-c", &format!("DROP SCHEMA IF EXISTS {} CASCADE;", stale.join(", "))])
+                .output();
+        }
+    });
+}
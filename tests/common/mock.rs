@@ -0,0 +1,509 @@
+//! An in-process fake PostgreSQL server speaking protocol version 3, for
+//! testing psql's side of COPY without a real server.
+//!
+//! It accepts any startup packet without authentication, answers simple
+//! queries, enters CopyIn for `COPY ... FROM STDIN` and CopyOut for
+//! `COPY ... TO STDOUT`, and records every message psql sends. Messages that
+//! a real server would reject during COPY, such as a Query before CopyDone,
+//! are kept as protocol errors.
+
+use crate::common::isolation;
+use crate::common::wire::{self, cstring, Message, Sender};
+use std::io::{self, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+pub const USER: &str = "mock";
+pub const DATABASE: &str = "mock";
+const PORT: &str = "5432";
+const SERVER_VERSION: &str = "17.0";
+
+/// What the backend answers to a query other than COPY.
+#[derive(Clone, Debug)]
+pub enum Response {
+    /// A CommandComplete with this tag, e.g. `"CREATE TABLE"`.
+    Command(String),
+    /// A text-format result set; every column is reported as `text`.
+    Rows {
+        columns: Vec<String>,
+        rows: Vec<Vec<Option<String>>>,
+    },
+    /// An ErrorResponse with this message.
+    Error(String),
+}
+
+struct Shared {
+    messages: Mutex<Vec<Message>>,
+    responses: Mutex<Vec<(String, Response)>>,
+    copy_out: Mutex<Vec<u8>>,
+    protocol_errors: Mutex<Vec<String>>,
+    superuser: AtomicBool,
+    connections: AtomicUsize,
+    stopped: AtomicBool,
+}
+
+pub struct MockBackend {
+    dir: TempDir,
+    shared: Arc<Shared>,
+}
+
+impl MockBackend {
+    /// Starts a backend. psql run by the calling test afterwards doesn't get
+    /// a schema of its own on the test server.
+    pub fn start() -> io::Result<Self> {
+        isolation::use_own_server();
+        let dir = TempDir::new()?;
+        let listener = UnixListener::bind(dir.path().join(format!(".s.PGSQL.{}", PORT)))?;
+        let shared = Arc::new(Shared {
+            messages: Mutex::new(Vec::new()),
+            responses: Mutex::new(Vec::new()),
+            copy_out: Mutex::new(b"1\t2\n3\t4\n".to_vec()),
+            protocol_errors: Mutex::new(Vec::new()),
+            superuser: AtomicBool::new(true),
+            connections: AtomicUsize::new(0),
+            stopped: AtomicBool::new(false),
+        });
+        let accepting = Arc::clone(&shared);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if accepting.stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else { continue };
+                let shared = Arc::clone(&accepting);
+                shared.connections.fetch_add(1, Ordering::SeqCst);
+                thread::spawn(move || {
+                    let _ = serve(stream, &shared);
+                    shared.connections.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        Ok(Self { dir, shared })
+    }
+
+    /// Answer queries starting with `prefix` (case-insensitively) with `response`.
+    pub fn respond(&self, prefix: &str, response: Response) {
+        self.shared.responses.lock().unwrap().push((prefix.to_lowercase(), response));
+    }
+
+    /// The data sent for every `COPY ... TO STDOUT`, one CopyData per line.
+    pub fn set_copy_out(&self, data: &[u8]) {
+        *self.shared.copy_out.lock().unwrap() = data.to_vec();
+    }
+
+    /// Whether later connections are reported as a superuser, which is what
+    /// psql's `%#` prompt escape shows.
+    pub fn set_superuser(&self, superuser: bool) {
+        self.shared.superuser.store(superuser, Ordering::SeqCst);
+    }
+
+    /// Connection options for psql, to go before any other arguments.
+    pub fn psql_args(&self) -> Vec<String> {
+        ["-X", "-h", &self.dir.path().to_string_lossy(), "-p", PORT, "-U", USER, "-d", DATABASE]
+            .map(String::from)
+            .to_vec()
+    }
+
+    /// Every message psql has sent so far, startup packet included.
+    pub fn messages(&self) -> Vec<Message> {
+        self.shared.messages.lock().unwrap().clone()
+    }
+
+    /// The payloads of all CopyData messages psql has sent, in order.
+    pub fn copy_data(&self) -> Vec<Vec<u8>> {
+        self.messages()
+            .into_iter()
+            .filter(|m| m.tag == Some(wire::COPY_DATA))
+            .map(|m| m.body)
+            .collect()
+    }
+
+    /// Every message psql has sent so far, one per line.
+    pub fn transcript(&self) -> String {
+        self.messages()
+            .iter()
+            .map(|m| format!("F {}\n", m.describe(Sender::Frontend)))
+            .collect()
+    }
+
+    /// What psql did that a server in its state would not accept, such as
+    /// sending a Query or closing the connection in the middle of a COPY.
+    pub fn protocol_errors(&self) -> Vec<String> {
+        self.shared.protocol_errors.lock().unwrap().clone()
+    }
+
+    /// Waits up to `timeout` for every connection to close, so that all psql
+    /// sent before exiting has been recorded. Returns whether they closed.
+    pub fn wait_for_disconnect(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.shared.connections.load(Ordering::SeqCst) > 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        true
+    }
+
+    /// The CopyDone or CopyFail psql ended the last COPY with, if any.
+    pub fn copy_end(&self) -> Option<Message> {
+        self.messages()
+            .into_iter()
+            .rev()
+            .find(|m| m.tag == Some(wire::COPY_DONE) || m.tag == Some(wire::COPY_FAIL))
+    }
+}
+
+impl Drop for MockBackend {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        let _ = UnixStream::connect(self.dir.path().join(format!(".s.PGSQL.{}", PORT)));
+    }
+}
+
+fn serve(stream: UnixStream, shared: &Shared) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let record = |message: &Message| shared.messages.lock().unwrap().push(message.clone());
+
+    loop {
+        let Some(message) = Message::read_startup(&mut reader)? else {
+            return Ok(());
+        };
+        record(&message);
+        match message.startup_code() {
+            Some(wire::SSL_REQUEST_CODE) | Some(wire::GSSENC_REQUEST_CODE) => writer.write_all(b"N")?,
+            Some(wire::CANCEL_REQUEST_CODE) => return Ok(()),
+            _ => break,
+        }
+    }
+
+    let mut out = Vec::new();
+    out.extend(authentication_ok());
+    for (name, value) in [
+        ("server_version", SERVER_VERSION),
+        ("server_encoding", "UTF8"),
+        ("client_encoding", "UTF8"),
+        ("DateStyle", "ISO, MDY"),
+        ("integer_datetimes", "on"),
+        ("standard_conforming_strings", "on"),
+        ("is_superuser", if shared.superuser.load(Ordering::SeqCst) { "on" } else { "off" }),
+        ("session_authorization", USER),
+    ] {
+        out.extend(parameter_status(name, value));
+    }
+    out.extend(Message::new(b'K', [1234u32.to_be_bytes(), 5678u32.to_be_bytes()].concat()).to_bytes());
+    out.extend(ready_for_query());
+    writer.write_all(&out)?;
+
+    while let Some(message) = Message::read(&mut reader)? {
+        record(&message);
+        match message.tag {
+            Some(wire::QUERY) => {
+                let query = cstring(&message.body);
+                let out = answer(&query, &mut reader, &mut writer, shared, &record)?;
+                writer.write_all(&out)?;
+            }
+            Some(wire::TERMINATE) => return Ok(()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// A `COPY ... FROM STDIN` or `COPY ... TO STDOUT` statement.
+struct Copy {
+    from_stdin: bool,
+    binary: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// An unquoted word, folded to lower case.
+    Word(String),
+    /// A double-quoted identifier or a string literal, unescaped.
+    Quoted(String),
+    Punct(char),
+}
+
+fn tokenize(sql: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                let mut text = String::new();
+                while let Some(next) = chars.next() {
+                    if next == c {
+                        if chars.peek() != Some(&c) {
+                            break;
+                        }
+                        chars.next();
+                    }
+                    text.push(next);
+                }
+                tokens.push(Token::Quoted(text));
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut word = c.to_lowercase().to_string();
+                while let Some(&next) = chars.peek() {
+                    if !(next.is_alphanumeric() || next == '_' || next == '$') {
+                        break;
+                    }
+                    word.extend(next.to_lowercase());
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+            c if c.is_whitespace() => {}
+            c => tokens.push(Token::Punct(c)),
+        }
+    }
+    tokens
+}
+
+/// Skips tokens up to and including the `)` closing an already consumed `(`.
+fn skip_parens(tokens: &mut impl Iterator<Item = Token>) -> Option<()> {
+    let mut depth = 1;
+    while depth > 0 {
+        match tokens.next()? {
+            Token::Punct('(') => depth += 1,
+            Token::Punct(')') => depth -= 1,
+            _ => {}
+        }
+    }
+    Some(())
+}
+
+/// Recognizes `COPY table [(columns)] FROM STDIN` and `COPY {table | (query)}
+/// TO STDOUT`, with either a `WITH (option value, ...)` list or the old bare
+/// option keywords, so that a table or column merely named like an option
+/// doesn't change the format.
+fn parse_copy(query: &str) -> Option<Copy> {
+    let word = |w: &str| Token::Word(w.to_string());
+    let mut tokens = tokenize(query).into_iter().peekable();
+    if tokens.next()? != word("copy") {
+        return None;
+    }
+    match tokens.next()? {
+        Token::Punct('(') => skip_parens(&mut tokens)?,
+        Token::Word(_) | Token::Quoted(_) => {
+            while tokens.next_if_eq(&Token::Punct('.')).is_some() {
+                tokens.next()?;
+            }
+            if tokens.next_if_eq(&Token::Punct('(')).is_some() {
+                skip_parens(&mut tokens)?;
+            }
+        }
+        Token::Punct(_) => return None,
+    }
+    let from_stdin = match (tokens.next()?, tokens.next()?) {
+        (from, stdin) if from == word("from") && stdin == word("stdin") => true,
+        (to, stdout) if to == word("to") && stdout == word("stdout") => false,
+        _ => return None,
+    };
+    tokens.next_if_eq(&word("with"));
+    let mut binary = false;
+    if tokens.next_if_eq(&Token::Punct('(')).is_some() {
+        loop {
+            let name = tokens.next()?;
+            let mut value = Vec::new();
+            let mut depth = 0;
+            let last = loop {
+                match tokens.next()? {
+                    Token::Punct(c @ (',' | ')')) if depth == 0 => break c,
+                    token => {
+                        match token {
+                            Token::Punct('(') => depth += 1,
+                            Token::Punct(')') => depth -= 1,
+                            _ => {}
+                        }
+                        value.push(token);
+                    }
+                }
+            };
+            if name == word("format") {
+                binary = matches!(value.as_slice(), [Token::Word(v) | Token::Quoted(v)] if v.eq_ignore_ascii_case("binary"));
+            }
+            if last == ')' {
+                break;
+            }
+        }
+    } else {
+        binary = tokens.any(|token| token == word("binary"));
+    }
+    Some(Copy { from_stdin, binary })
+}
+
+fn answer(
+    query: &str,
+    reader: &mut BufReader<UnixStream>,
+    writer: &mut UnixStream,
+    shared: &Shared,
+    record: &dyn Fn(&Message),
+) -> io::Result<Vec<u8>> {
+    let lower = query.trim().to_lowercase();
+    let copy = parse_copy(query);
+    let mut out = Vec::new();
+    if lower.is_empty() {
+        out.extend(Message::new(b'I', Vec::new()).to_bytes());
+    } else if let Some(Copy { from_stdin: true, binary }) = copy {
+        writer.write_all(&Message::new(b'G', vec![binary as u8, 0, 0]).to_bytes())?;
+        let mut data = Vec::new();
+        loop {
+            let Some(message) = Message::read(reader)? else {
+                shared.protocol_errors.lock().unwrap().push("connection closed during COPY FROM STDIN".to_string());
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            };
+            record(&message);
+            match message.tag {
+                Some(wire::COPY_DATA) => data.extend(message.body),
+                Some(wire::COPY_DONE) => {
+                    let rows = if binary { binary_rows(&data) } else { text_rows(&data) };
+                    out.extend(command_complete(&format!("COPY {}", rows)));
+                    break;
+                }
+                Some(wire::COPY_FAIL) => {
+                    out.extend(error_response(&format!("COPY from stdin failed: {}", cstring(&message.body))));
+                    break;
+                }
+                // A server ends the COPY with an error, so whatever psql
+                // sends next goes to a session that isn't where psql thinks.
+                Some(tag @ (wire::QUERY | wire::TERMINATE)) => {
+                    shared
+                        .protocol_errors
+                        .lock()
+                        .unwrap()
+                        .push(format!("{} during COPY FROM STDIN", message.describe(Sender::Frontend)));
+                    out.extend(error_response(&format!("unexpected message type 0x{:02X} during COPY from stdin", tag)));
+                    break;
+                }
+                _ => {}
+            }
+        }
+    } else if let Some(Copy { from_stdin: false, binary }) = copy {
+        let data = shared.copy_out.lock().unwrap().clone();
+        out.extend(Message::new(b'H', vec![binary as u8, 0, 0]).to_bytes());
+        let lines: Vec<&[u8]> = data.split_inclusive(|&b| b == b'\n').collect();
+        for line in &lines {
+            out.extend(Message::new(wire::COPY_DATA, line.to_vec()).to_bytes());
+        }
+        out.extend(Message::new(wire::COPY_DONE, Vec::new()).to_bytes());
+        out.extend(command_complete(&format!("COPY {}", lines.len())));
+    } else {
+        let response = shared
+            .responses
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(prefix, _)| lower.starts_with(prefix))
+            .map(|(_, response)| response.clone())
+            .unwrap_or_else(|| Response::Command(lower.split_whitespace().next().unwrap().to_uppercase()));
+        match response {
+            Response::Command(tag) => out.extend(command_complete(&tag)),
+            Response::Rows { columns, rows } => {
+                out.extend(row_description(&columns));
+                for row in &rows {
+                    out.extend(data_row(row));
+                }
+                out.extend(command_complete(&format!("SELECT {}", rows.len())));
+            }
+            Response::Error(message) => out.extend(error_response(&message)),
+        }
+    }
+    out.extend(ready_for_query());
+    Ok(out)
+}
+
+/// Counts the rows of text or CSV COPY data, up to an end-of-data marker.
+fn text_rows(data: &[u8]) -> usize {
+    data.split_inclusive(|&b| b == b'\n')
+        .take_while(|line| *line != b"\\.\n" && *line != b"\\.\r\n" && *line != b"\\.")
+        .count()
+}
+
+/// Counts the tuples of binary COPY data, or 0 if it is not well formed.
+fn binary_rows(data: &[u8]) -> usize {
+    let int = |at: usize, len: usize| -> Option<i64> {
+        let bytes = data.get(at..at + len)?;
+        Some(bytes.iter().fold(0i64, |acc, &b| (acc << 8) | b as i64))
+    };
+    let Some(extension) = int(15, 4) else { return 0 };
+    let mut at = 19 + extension as usize;
+    let mut rows = 0;
+    while let Some(fields) = int(at, 2) {
+        at += 2;
+        if fields == 0xffff {
+            return rows;
+        }
+        for _ in 0..fields {
+            let Some(len) = int(at, 4) else { return 0 };
+            at += 4;
+            if len != 0xffff_ffff {
+                at += len as usize;
+            }
+        }
+        rows += 1;
+    }
+    0
+}
+
+fn authentication_ok() -> Vec<u8> {
+    Message::new(b'R', 0u32.to_be_bytes().to_vec()).to_bytes()
+}
+
+fn parameter_status(name: &str, value: &str) -> Vec<u8> {
+    Message::new(b'S', [name.as_bytes(), b"\0", value.as_bytes(), b"\0"].concat()).to_bytes()
+}
+
+fn ready_for_query() -> Vec<u8> {
+    Message::new(b'Z', b"I".to_vec()).to_bytes()
+}
+
+fn command_complete(tag: &str) -> Vec<u8> {
+    Message::new(b'C', [tag.as_bytes(), b"\0"].concat()).to_bytes()
+}
+
+fn error_response(message: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (field, value) in [(b'S', "ERROR"), (b'V', "ERROR"), (b'C', "XX000"), (b'M', message)] {
+        body.push(field);
+        body.extend_from_slice(value.as_bytes());
+        body.push(0);
+    }
+    body.push(0);
+    Message::new(b'E', body).to_bytes()
+}
+
+fn row_description(columns: &[String]) -> Vec<u8> {
+    let mut body = (columns.len() as u16).to_be_bytes().to_vec();
+    for column in columns {
+        body.extend_from_slice(column.as_bytes());
+        body.push(0);
+        body.extend_from_slice(&0u32.to_be_bytes()); // table oid
+        body.extend_from_slice(&0u16.to_be_bytes()); // column number
+        body.extend_from_slice(&25u32.to_be_bytes()); // text
+        body.extend_from_slice(&(-1i16).to_be_bytes()); // variable length
+        body.extend_from_slice(&(-1i32).to_be_bytes()); // no typmod
+        body.extend_from_slice(&0u16.to_be_bytes()); // text format
+    }
+    Message::new(b'T', body).to_bytes()
+}
+
+fn data_row(row: &[Option<String>]) -> Vec<u8> {
+    let mut body = (row.len() as u16).to_be_bytes().to_vec();
+    for value in row {
+        match value {
+            Some(value) => {
+                body.extend_from_slice(&(value.len() as u32).to_be_bytes());
+                body.extend_from_slice(value.as_bytes());
+            }
+            None => body.extend_from_slice(&(-1i32).to_be_bytes()),
+        }
+    }
+    Message::new(b'D', body).to_bytes()
+}
@@ -0,0 +1,406 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+pub use std::process::Output;
+use std::process::{Command, Stdio};
+use uuid::Uuid;
+use once_cell::sync::OnceCell;
+pub use isolation::{limit, test_dir};
+pub use pgcopy::PgCopy;
+pub use profile::Profile;
+pub use report::Cell;
+pub use screen::{set_timeout, spawn_session, PtySession, Screen};
+pub use timing::rerun;
+
+pub mod client;
+pub mod cluster;
+pub mod diff;
+pub mod fixture;
+pub mod html;
+pub mod isolation;
+pub mod lifecycle;
+pub mod mock;
+pub mod pgcopy;
+pub mod profile;
+pub mod proxy;
+pub mod report;
+pub mod screen;
+#[macro_use]
+pub mod timing;
+pub mod wire;
+
+/// Checks that `$content` is exactly `$expected_str`, without the first line
+/// break if it starts with one, and prints a diff otherwise.
+#[macro_export]
+macro_rules! verify {
+    ($content:expr, $expected_str:expr) => {{
+        let content = $content;
+        let content: &[u8] = AsRef::<[u8]>::as_ref(&content);
+        let expected: &str = $expected_str;
+        let expected = expected.strip_prefix('\n').unwrap_or(expected);
+
+        if content != expected.as_bytes() {
+            println!("\nUnexpected output at {}:{}", file!(), line!());
+            let diff = $crate::common::diff::render_diff(content, expected.as_bytes());
+            print!("{}", diff.colored);
+            $crate::common::report::record_diff(diff.plain);
+            panic!("Verification failed");
+        }
+    }};
+}
+
+/// Checks that `$actual` is the same binary COPY file as `$expected`, both
+/// parsing cleanly, and prints their structural differences otherwise.
+#[macro_export]
+macro_rules! verify_pgcopy {
+    ($actual:expr, $expected:expr) => {{
+        let (actual, expected) = ($actual, $expected);
+        let actual: &[u8] = AsRef::<[u8]>::as_ref(&actual);
+        let expected: &[u8] = AsRef::<[u8]>::as_ref(&expected);
+        let same = match ($crate::common::pgcopy::parse(actual), $crate::common::pgcopy::parse(expected)) {
+            (Ok(actual), Ok(expected)) => actual == expected,
+            _ => false,
+        };
+        if !same {
+            println!("\nUnexpected binary COPY data at {}:{}", file!(), line!());
+            let diff = $crate::common::diff::render_diff(actual, expected);
+            print!("{}", diff.colored);
+            $crate::common::report::record_diff(diff.plain);
+            panic!("Verification failed");
+        }
+    }};
+}
+
+#[macro_export]
+macro_rules! verify_contains {
+    ($content:expr, $needle:expr) => {{
+        let content = &$content;
+        let content_str = String::from_utf8_lossy(AsRef::<[u8]>::as_ref(content));
+        if !content_str.contains($needle) {
+            println!("\nUnexpected output at {}:{}", file!(), line!());
+            println!("Output:\n{}", content_str);
+            println!("Failed to find expected text: {}", $needle);
+            $crate::common::report::record_diff(format!("Expected to find {:?} in:\n{}", $needle, content_str));
+            panic!("Verification failed");
+        }
+    }};
+}
+
+#[macro_export]
+macro_rules! expect {
+    // The shared body; `$transcript` is `None` or `Some` closure returning
+    // the protocol messages to print on failure.
+    (@transcript $session:expr, $pattern:expr, $log_file:expr, $transcript:expr) => {{
+        let pattern = $pattern;
+        let location = concat!(file!(), ":", line!());
+        if !$crate::common::screen::step($session, location, format!("output {:?}", pattern), |session| {
+            session.expect(&pattern).is_ok()
+        }) {
+            println!("Unexpected output at {}", location);
+            $crate::common::report_session(&$session.get_stream().screen(), $log_file.path());
+            if let Some(transcript) = $transcript {
+                println!("Protocol messages at time of failure:\n{}", transcript());
+            }
+            println!("Failed to find expected pattern: {}", pattern);
+            panic!("Expectation failed");
+        }
+    }};
+    ($session:expr, $pattern:expr, $log_file:expr) => {
+        $crate::expect!(@transcript $session, $pattern, $log_file, None::<&dyn Fn() -> String>)
+    };
+    // Also prints the protocol messages seen by a Proxy or MockBackend.
+    ($session:expr, $pattern:expr, $log_file:expr, $wire:expr) => {
+        $crate::expect!(@transcript $session, $pattern, $log_file, Some(|| $wire.transcript()))
+    };
+}
+
+/// Waits until `$text` appears anywhere on the rendered screen.
+#[macro_export]
+macro_rules! expect_screen {
+    (@transcript $session:expr, $text:expr, $log_file:expr, $transcript:expr) => {{
+        let text: &str = $text;
+        let location = concat!(file!(), ":", line!());
+        if !$crate::common::screen::step($session, location, format!("screen {:?}", text), |session| {
+            let timeout = session.get_stream().screen().timeout();
+            $crate::common::screen::wait_for_screen(session, timeout, |screen| screen.contents().contains(text))
+        }) {
+            println!("Unexpected screen at {}", location);
+            $crate::common::report_session(&$session.get_stream().screen(), $log_file.path());
+            if let Some(transcript) = $transcript {
+                println!("Protocol messages at time of failure:\n{}", transcript());
+            }
+            println!("Failed to find expected text on screen: {}", text);
+            panic!("Expectation failed");
+        }
+    }};
+    ($session:expr, $text:expr, $log_file:expr) => {
+        $crate::expect_screen!(@transcript $session, $text, $log_file, None::<&dyn Fn() -> String>)
+    };
+    ($session:expr, $text:expr, $log_file:expr, $wire:expr) => {
+        $crate::expect_screen!(@transcript $session, $text, $log_file, Some(|| $wire.transcript()))
+    };
+}
+
+/// Waits until the cursor sits right after `$prompt` at the start of a row,
+/// i.e. psql is showing that prompt and nothing has been typed yet.
+#[macro_export]
+macro_rules! expect_prompt {
+    (@transcript $session:expr, $prompt:expr, $log_file:expr, $transcript:expr) => {{
+        let prompt: &str = $prompt;
+        let location = concat!(file!(), ":", line!());
+        if !$crate::common::screen::step($session, location, format!("prompt {:?}", prompt), |session| {
+            let timeout = session.get_stream().screen().timeout();
+            $crate::common::screen::wait_for_screen(session, timeout, |screen| screen.before_cursor() == prompt)
+        }) {
+            println!("Unexpected screen at {}", location);
+            $crate::common::report_session(&$session.get_stream().screen(), $log_file.path());
+            if let Some(transcript) = $transcript {
+                println!("Protocol messages at time of failure:\n{}", transcript());
+            }
+            println!("Failed to find expected prompt before the cursor: {:?}", prompt);
+            panic!("Expectation failed");
+        }
+    }};
+    ($session:expr, $prompt:expr, $log_file:expr) => {
+        $crate::expect_prompt!(@transcript $session, $prompt, $log_file, None::<&dyn Fn() -> String>)
+    };
+    ($session:expr, $prompt:expr, $log_file:expr, $wire:expr) => {
+        $crate::expect_prompt!(@transcript $session, $prompt, $log_file, Some(|| $wire.transcript()))
+    };
+}
+
+#[macro_export]
+macro_rules! expect_exit_status {
+    ($output:expr, $code:expr) => {{
+        let code = $output.status.code();
+        if code != Some($code) {
+            println!("\nUnexpected exit status at {}:{}", file!(), line!());
+            println!("Expected exit status {}, got {:?}", $code, code);
+            println!("stdout: {}", String::from_utf8_lossy(&$output.stdout));
+            println!("stderr: {}", String::from_utf8_lossy(&$output.stderr));
+            $crate::common::report::record_diff(format!(
+                "Expected exit status {}, got {:?}\nstdout: {}\nstderr: {}",
+                $code,
+                code,
+                String::from_utf8_lossy(&$output.stdout),
+                String::from_utf8_lossy(&$output.stderr)
+            ));
+            panic!("Exit status verification failed");
+        }
+    }};
+}
+
+#[macro_export]
+macro_rules! isempty {
+    ($content:expr) => {{
+        verify!($content, "\n");
+    }};
+}
+
+#[macro_export]
+macro_rules! expect_copy_two {
+    ($output:expr) => {{
+        expect_exit_status!($output, 0);
+        verify!(
+            $output.stdout,
+            r#"
+COPY 2
+"#
+        );
+        isempty!($output.stderr);
+    }};
+}
+
+#[macro_export]
+macro_rules! expect_insert_two {
+    ($output:expr) => {{
+        expect_exit_status!($output, 0);
+        verify!(
+            $output.stdout,
+            r#"
+INSERT 0 2
+"#
+        );
+        isempty!($output.stderr);
+    }};
+}
+
+#[macro_export]
+macro_rules! expect_create_table {
+    ($output:expr) => {{
+        expect_exit_status!($output, 0);
+        verify!(
+            $output.stdout,
+            r#"
+CREATE TABLE
+"#
+        );
+        isempty!($output.stderr);
+    }};
+}
+
+#[macro_export]
+macro_rules! expect_drop_table {
+    ($output:expr) => {{
+        expect_exit_status!($output, 0);
+        verify!(
+            $output.stdout,
+            r#"
+DROP TABLE
+"#
+        );
+        isempty!($output.stderr);
+    }};
+}
+
+#[macro_export]
+macro_rules! expect_result_set {
+    ($output:expr) => {{
+        expect_exit_status!($output, 0);
+        verify!($output.stdout, r#"
+ c1 | c2 
+----+----
+  1 |  2
+  3 |  4
+(2 rows)
+
+"#);
+        isempty!($output.stderr);
+    }};
+}
+
+/// The prompts interactive psql is started with. They don't depend on the
+/// database name, superuser status or psqlrc, so waiting for them is
+/// deterministic.
+pub const PROMPT1: &str = "psql_tester1> ";
+pub const PROMPT2: &str = "psql_tester2> ";
+pub const PROMPT3: &str = "psql_tester3> ";
+
+/// psql for an interactive session: no psqlrc, the `PROMPT*` above and
+/// history in /dev/null rather than ~/.psql_history, so `\s` only shows this
+/// session's lines.
+pub fn psql_command() -> Command {
+    let mut command = Command::new("psql");
+    command.arg("-X");
+    for (name, value) in [("PROMPT1", PROMPT1), ("PROMPT2", PROMPT2), ("PROMPT3", PROMPT3)] {
+        command.arg("-v").arg(format!("{}={}", name, value));
+    }
+    command.env("PSQL_HISTORY", "/dev/null");
+    isolation::isolate(&mut command);
+    command
+}
+
+/// Removed at exit by [`lifecycle::teardown`].
+static TEST_ENVIRONMENT: OnceCell<TestEnvironment> = OnceCell::new();
+
+pub struct TestEnvironment {
+    pub temp_dir: PathBuf,
+    pub file_path_text: String,
+    pub file_path_binary: String,
+    pub file_path_csv: String,
+}
+
+impl TestEnvironment {
+    fn new() -> Self {
+        lifecycle::setup();
+        let temp_dir = tempfile::Builder::new()
+            .prefix(&format!("{}{}-", lifecycle::DIR_PREFIX, std::process::id()))
+            .tempdir()
+            .unwrap()
+            .into_path();
+        let test_table = Uuid::new_v4();
+        let base_file = temp_dir.join(test_table.to_string());
+        let file_path_text = base_file
+            .with_extension("text")
+            .to_string_lossy()
+            .into_owned();
+        let file_path_binary = base_file
+            .with_extension("binary")
+            .to_string_lossy()
+            .into_owned();
+        let file_path_csv = base_file
+            .with_extension("csv")
+            .to_string_lossy()
+            .into_owned();
+
+        // The rows (1, 2) and (3, 4) of two int8 columns, as the server's
+        // COPY TO writes them; the `fixture` tests check that it does.
+        let rows: &[&[Option<&str>]] = &[&[Some("1"), Some("2")], &[Some("3"), Some("4")]];
+        fs::write(&file_path_text, fixture::text(rows)).unwrap();
+        fs::write(&file_path_csv, fixture::csv(rows)).unwrap();
+        fs::write(&file_path_binary, PgCopy::int8(&[&[Some(1), Some(2)], &[Some(3), Some(4)]]).to_bytes()).unwrap();
+
+        Self {
+            temp_dir,
+            file_path_text,
+            file_path_binary,
+            file_path_csv,
+        }
+    }
+
+    fn cleanup(&self) {
+        let _ = fs::remove_dir_all(&self.temp_dir);
+    }
+}
+
+pub fn get_test_environment() -> &'static TestEnvironment {
+    TEST_ENVIRONMENT.get_or_init(TestEnvironment::new)
+}
+
+/// Writes the rows `(1, 1)` through `(rows, rows)` to `path` in text format.
+pub fn write_series_file(path: &Path, rows: usize) -> io::Result<()> {
+    let output = run_cmd("psql", &["-c", &format!(r#"\copy (SELECT g, g FROM generate_series(1, {}) g) to '{}'"#, rows, path.display())])?;
+    expect_exit_status!(output, 0);
+    Ok(())
+}
+
+pub fn run_cmd(program: &str, args: &[&str]) -> io::Result<Output> {
+    let mut command = Command::new(program);
+    isolation::isolate(&mut command);
+    let _permit = limit("psql");
+    let output = command.args(args).output()?;
+    report_failure(program, args, &output);
+    Ok(output)
+}
+
+pub fn run_cmd_with_input(program: &str, args: &[&str], input: &[u8]) -> io::Result<Output> {
+    let mut command = Command::new(program);
+    isolation::isolate(&mut command);
+    let _permit = limit("psql");
+    let mut child = command
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // psql may exit before reading all of its input, so a broken pipe is not an error here.
+    let _ = child.stdin.take().unwrap().write_all(input);
+    let output = child.wait_with_output()?;
+    report_failure(program, args, &output);
+    Ok(output)
+}
+
+/// Prints the rendered screen of a PTY session, how long each of its steps
+/// took with the last output read, and its raw log of reads and writes if
+/// `PSQL_TEST_RAW_LOG` is set. All of it goes into the cell's report.
+pub fn report_session(screen: &Screen, log_path: &Path) {
+    println!("Screen at time of failure:\n{}", screen.dump());
+    println!("Steps:\n{}", screen.timing_report());
+    let logs = fs::read_to_string(log_path).unwrap_or_default();
+    if std::env::var_os("PSQL_TEST_RAW_LOG").is_some() {
+        println!("Session logs at time of failure:\n{}", logs);
+    }
+    report::record_session_log(report::session_log(screen, &logs));
+}
+
+fn report_failure(program: &str, args: &[&str], output: &Output) {
+    if !output.status.success() {
+        println!("Failed command: {} {}", program, args.join(" "));
+        if !output.stdout.is_empty() {
+            println!("stdout: {}", String::from_utf8_lossy(&output.stdout));
+        }
+        if !output.stderr.is_empty() {
+            println!("stderr: {}", String::from_utf8_lossy(&output.stderr));
+        }
+    }
+}
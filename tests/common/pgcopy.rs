@@ -0,0 +1,300 @@
+//! The binary format of `COPY ... (FORMAT binary)`: an 11-byte signature, a
+//! flags word, a header extension, tuples of length-prefixed fields and a
+//! trailer. [`parse`] validates all of it and [`differences`] compares two
+//! files field by field, so a failure says "tuple 2 field 1 length 8 vs 4"
+//! rather than showing two hex dumps. [`PgCopy::to_bytes`] writes the format
+//! without a server, and [`PgCopy::corrupted`] breaks it on purpose.
+
+use std::fmt;
+
+pub const SIGNATURE: &[u8; 11] = b"PGCOPY\n\xff\r\n\0";
+
+/// Bit 16 of the flags: every tuple has an OID field. Servers dropped
+/// `WITH OIDS` in PostgreSQL 12 and reject files with it, and so does [`parse`].
+pub const FLAG_OIDS: u32 = 1 << 16;
+
+/// A parsed binary COPY file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgCopy {
+    pub flags: u32,
+    pub extension: Vec<u8>,
+    /// Each tuple's fields, `None` for NULL.
+    pub tuples: Vec<Vec<Option<Vec<u8>>>>,
+}
+
+impl PgCopy {
+    /// A file with no flags or header extension.
+    pub fn new(tuples: Vec<Vec<Option<Vec<u8>>>>) -> Self {
+        Self {
+            flags: 0,
+            extension: Vec::new(),
+            tuples,
+        }
+    }
+
+    /// A file of `int8` columns, `None` for NULL.
+    pub fn int8(rows: &[&[Option<i64>]]) -> Self {
+        Self::new(
+            rows.iter()
+                .map(|row| row.iter().map(|value| value.map(|value| value.to_be_bytes().to_vec())).collect())
+                .collect(),
+        )
+    }
+
+    /// A file of `text` columns, `None` for NULL.
+    pub fn text(rows: &[&[Option<&str>]]) -> Self {
+        Self::new(
+            rows.iter()
+                .map(|row| row.iter().map(|value| value.map(|value| value.as_bytes().to_vec())).collect())
+                .collect(),
+        )
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = SIGNATURE.to_vec();
+        bytes.extend_from_slice(&self.flags.to_be_bytes());
+        bytes.extend_from_slice(&(self.extension.len() as i32).to_be_bytes());
+        bytes.extend_from_slice(&self.extension);
+        for tuple in &self.tuples {
+            bytes.extend_from_slice(&(tuple.len() as i16).to_be_bytes());
+            for field in tuple {
+                match field {
+                    Some(field) => {
+                        bytes.extend_from_slice(&(field.len() as i32).to_be_bytes());
+                        bytes.extend_from_slice(field);
+                    }
+                    None => bytes.extend_from_slice(&(-1i32).to_be_bytes()),
+                }
+            }
+        }
+        bytes.extend_from_slice(&(-1i16).to_be_bytes());
+        bytes
+    }
+
+    /// The file written with `corruption`.
+    pub fn corrupted(&self, corruption: Corruption) -> Vec<u8> {
+        let bytes = self.to_bytes();
+        let header = SIGNATURE.len() + 8 + self.extension.len();
+        let first_field = header + 2;
+        match corruption {
+            Corruption::Signature => [b"PGCOPY\n\xff\n\0".as_slice(), &bytes[SIGNATURE.len()..]].concat(),
+            Corruption::CriticalFlag => {
+                let mut bytes = bytes;
+                bytes[SIGNATURE.len()] |= 0x80;
+                bytes
+            }
+            Corruption::TruncatedTuple => bytes[..first_field + 4 + 1].to_vec(),
+            Corruption::FieldCount(count) => {
+                let mut bytes = bytes;
+                bytes[header..header + 2].copy_from_slice(&count.to_be_bytes());
+                bytes
+            }
+            Corruption::HugeLength => {
+                let mut bytes = bytes;
+                bytes[first_field..first_field + 4].copy_from_slice(&i32::MAX.to_be_bytes());
+                bytes
+            }
+            Corruption::MissingTrailer => bytes[..bytes.len() - 2].to_vec(),
+            Corruption::TrailingData => [bytes.as_slice(), b"junk"].concat(),
+        }
+    }
+}
+
+/// Ways [`PgCopy::corrupted`] breaks a file. Those touching a tuple change
+/// the first one, which must have a non-NULL first field.
+#[derive(Clone, Copy, Debug)]
+pub enum Corruption {
+    /// `\r` missing from the signature.
+    Signature,
+    /// Bit 31 of the flags, which no reader knows, set.
+    CriticalFlag,
+    /// The file ends one byte into the first field's data.
+    TruncatedTuple,
+    /// The first tuple claims this many fields.
+    FieldCount(i16),
+    /// The first field claims to be 2 GB long.
+    HugeLength,
+    MissingTrailer,
+    /// Bytes after the trailer.
+    TrailingData,
+}
+
+/// What is wrong with a binary COPY file and at which byte offset.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize, what: &str) -> Result<&'a [u8], ParseError> {
+        if self.bytes.len() - self.offset < len {
+            return Err(self.error(format!(
+                "{} needs {} bytes but only {} are left",
+                what,
+                len,
+                self.bytes.len() - self.offset
+            )));
+        }
+        let taken = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(taken)
+    }
+
+    fn i16(&mut self, what: &str) -> Result<i16, ParseError> {
+        Ok(i16::from_be_bytes(self.take(2, what)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self, what: &str) -> Result<i32, ParseError> {
+        Ok(i32::from_be_bytes(self.take(4, what)?.try_into().unwrap()))
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        ParseError {
+            offset: self.offset,
+            message,
+        }
+    }
+}
+
+/// Parses a whole binary COPY file. Every tuple must have as many fields as
+/// the first, and nothing may follow the trailer.
+pub fn parse(bytes: &[u8]) -> Result<PgCopy, ParseError> {
+    let mut reader = Reader { bytes, offset: 0 };
+    let signature = reader.take(SIGNATURE.len(), "signature").map_err(|_| ParseError {
+        offset: 0,
+        message: "file is shorter than the signature".to_string(),
+    })?;
+    if signature != SIGNATURE {
+        return Err(ParseError {
+            offset: 0,
+            message: format!("signature is \"{}\", not \"{}\"", signature.escape_ascii(), SIGNATURE.escape_ascii()),
+        });
+    }
+
+    let flags = reader.i32("flags")? as u32;
+    if flags & FLAG_OIDS != 0 {
+        reader.offset -= 4;
+        return Err(reader.error("OID flag set, but tuples have no OID fields since PostgreSQL 12".to_string()));
+    }
+    // Bits 16-31 are critical: a reader must reject any it doesn't know.
+    let unknown = flags & 0xffff_0000;
+    if unknown != 0 {
+        reader.offset -= 4;
+        return Err(reader.error(format!("unknown critical flags {:#010x}", unknown)));
+    }
+
+    let extension_len = reader.i32("header extension length")?;
+    if extension_len < 0 {
+        reader.offset -= 4;
+        return Err(reader.error(format!("header extension length is {}", extension_len)));
+    }
+    let extension = reader.take(extension_len as usize, "header extension")?.to_vec();
+
+    let mut tuples = Vec::new();
+    loop {
+        let tuple_number = tuples.len() + 1;
+        let start = reader.offset;
+        let count = reader.i16(&format!("tuple {} field count", tuple_number))?;
+        if count == -1 {
+            break;
+        }
+        let expected = tuples.first().map(|first: &Vec<Option<Vec<u8>>>| first.len());
+        if count < 0 || expected.is_some_and(|expected| expected != count as usize) {
+            reader.offset = start;
+            return Err(reader.error(match expected {
+                Some(expected) => format!("tuple {} has {} fields, tuple 1 has {}", tuple_number, count, expected),
+                None => format!("tuple {} has {} fields", tuple_number, count),
+            }));
+        }
+        let mut fields = Vec::new();
+        for field_number in 1..=count {
+            let what = format!("tuple {} field {}", tuple_number, field_number);
+            let len = reader.i32(&format!("{} length", what))?;
+            match len {
+                -1 => fields.push(None),
+                len if len < 0 => {
+                    reader.offset -= 4;
+                    return Err(reader.error(format!("{} length is {}", what, len)));
+                }
+                len => fields.push(Some(reader.take(len as usize, &what)?.to_vec())),
+            }
+        }
+        tuples.push(fields);
+    }
+    if reader.offset != bytes.len() {
+        return Err(reader.error(format!("{} bytes follow the trailer", bytes.len() - reader.offset)));
+    }
+    Ok(PgCopy { flags, extension, tuples })
+}
+
+/// Whether `bytes` starts like a binary COPY file.
+pub fn is_pgcopy(bytes: &[u8]) -> bool {
+    bytes.starts_with(SIGNATURE)
+}
+
+/// The structural differences from `actual` to `expected`, one line each,
+/// such as `tuple 2 field 1 length 8 vs 4`. Empty if they are equal.
+pub fn differences(actual: &PgCopy, expected: &PgCopy) -> Vec<String> {
+    let mut differences = Vec::new();
+    if actual.flags != expected.flags {
+        differences.push(format!("flags {:#010x} vs {:#010x}", actual.flags, expected.flags));
+    }
+    if actual.extension != expected.extension {
+        differences.push(format!(
+            "header extension length {} vs {}",
+            actual.extension.len(),
+            expected.extension.len()
+        ));
+    }
+    if actual.tuples.len() != expected.tuples.len() {
+        differences.push(format!("{} tuples vs {}", actual.tuples.len(), expected.tuples.len()));
+    }
+    for (i, (actual, expected)) in actual.tuples.iter().zip(&expected.tuples).enumerate() {
+        let tuple = i + 1;
+        if actual.len() != expected.len() {
+            differences.push(format!("tuple {} has {} fields vs {}", tuple, actual.len(), expected.len()));
+        }
+        for (j, (actual, expected)) in actual.iter().zip(expected).enumerate() {
+            let field = j + 1;
+            match (actual, expected) {
+                (None, None) => {}
+                (Some(_), None) => differences.push(format!("tuple {} field {} is not NULL vs NULL", tuple, field)),
+                (None, Some(_)) => differences.push(format!("tuple {} field {} is NULL vs not NULL", tuple, field)),
+                (Some(actual), Some(expected)) if actual.len() != expected.len() => differences.push(format!(
+                    "tuple {} field {} length {} vs {}",
+                    tuple,
+                    field,
+                    actual.len(),
+                    expected.len()
+                )),
+                (Some(actual), Some(expected)) if actual != expected => differences.push(format!(
+                    "tuple {} field {} is {} vs {}",
+                    tuple,
+                    field,
+                    hex(actual),
+                    hex(expected)
+                )),
+                _ => {}
+            }
+        }
+    }
+    differences
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,133 @@
+//! Line-editing profiles of interactive psql. Depending on how it was built
+//! and started, psql reads terminal input with GNU readline, with libedit, or
+//! with plain `fgets` (`-n`/--no-readline or a build without either library),
+//! and some of what a user sees differs between them.
+
+use crate::common::psql_command;
+use once_cell::sync::OnceCell;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Profile {
+    Readline,
+    Libedit,
+    NoReadline,
+}
+
+static DETECTED: OnceCell<Profile> = OnceCell::new();
+
+impl Profile {
+    /// The profile of psql started without `-n`. It is taken from
+    /// `PSQL_TEST_LINE_EDITOR` (`readline`, `libedit` or `none`) if set, and
+    /// otherwise from the library the psql binary links.
+    pub fn detected() -> Self {
+        *DETECTED.get_or_init(|| match env::var("PSQL_TEST_LINE_EDITOR") {
+            Ok(name) => Self::from_name(&name)
+                .unwrap_or_else(|| panic!("PSQL_TEST_LINE_EDITOR must be readline, libedit or none, not {:?}", name)),
+            Err(_) => linked_library(&psql_binary()),
+        })
+    }
+
+    /// The detected profile and, if that is not already it, `-n`.
+    pub fn all() -> Vec<Self> {
+        let mut all = vec![Self::detected()];
+        if Self::detected() != Self::NoReadline {
+            all.push(Self::NoReadline);
+        }
+        all
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "readline" => Some(Self::Readline),
+            "libedit" => Some(Self::Libedit),
+            "none" => Some(Self::NoReadline),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Readline => "readline",
+            Self::Libedit => "libedit",
+            Self::NoReadline => "none",
+        }
+    }
+
+    /// [`psql_command`] reading input this way.
+    pub fn command(&self) -> Command {
+        let mut command = psql_command();
+        if *self == Self::NoReadline {
+            command.arg("-n");
+        }
+        command
+    }
+
+    /// Whether the expectations below have been checked against a psql
+    /// using this library. Libedit is detected, but what it completes and
+    /// how `\s` prints its history haven't been, so the `line_editing` tests
+    /// skip it.
+    pub fn verified(&self) -> bool {
+        *self != Self::Libedit
+    }
+
+    /// Whether tab completes at the main prompt instead of inserting a tab.
+    pub fn completes(&self) -> bool {
+        match self {
+            Self::Readline => true,
+            Self::Libedit => unimplemented!("libedit's expectations are unverified"),
+            Self::NoReadline => false,
+        }
+    }
+
+    /// Whether lines typed at the main prompt are kept in the history that
+    /// `\s` prints, one per line.
+    pub fn keeps_history(&self) -> bool {
+        match self {
+            Self::Readline => true,
+            Self::Libedit => unimplemented!("libedit's expectations are unverified"),
+            Self::NoReadline => false,
+        }
+    }
+}
+
+/// The psql binary on PATH. Debian's /usr/bin/psql is a Perl wrapper that
+/// picks a version to run; in that case the binary is found through
+/// pg_config.
+fn psql_binary() -> PathBuf {
+    let path = env::var_os("PATH").unwrap_or_default();
+    let binary = env::split_paths(&path)
+        .map(|dir| dir.join("psql"))
+        .find(|candidate| candidate.is_file())
+        .expect("psql not found on PATH");
+    if !fs::read(&binary).map(|bytes| bytes.starts_with(b"#!")).unwrap_or(false) {
+        return binary;
+    }
+    let output = Command::new("pg_config").arg("--bindir").output();
+    match output {
+        Ok(output) if output.status.success() => {
+            PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()).join("psql")
+        }
+        _ => binary,
+    }
+}
+
+/// The line-editing library `binary` links, from ldd or, on macOS, otool.
+fn linked_library(binary: &Path) -> Profile {
+    let output = if cfg!(target_os = "macos") {
+        Command::new("otool").arg("-L").arg(binary).output()
+    } else {
+        Command::new("ldd").arg(binary).output()
+    };
+    let libraries = output.map(|output| String::from_utf8_lossy(&output.stdout).into_owned()).unwrap_or_default();
+    if libraries.contains("libreadline") {
+        Profile::Readline
+    } else if libraries.contains("libedit") {
+        Profile::Libedit
+    } else {
+        Profile::NoReadline
+    }
+}
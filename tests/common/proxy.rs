@@ -0,0 +1,343 @@
+//! A local TCP proxy between psql and the test server. It records every
+//! message in both directions, and can break the connection at a chosen point
+//! for testing how psql handles lost connections.
+
+use crate::common::run_cmd;
+use crate::common::wire::{self, Message, Sender};
+use once_cell::sync::OnceCell;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Where in the frontend message stream a fault fires.
+#[derive(Clone, Copy, Debug)]
+pub enum Point {
+    /// Once this many bytes of CopyData payload have been forwarded.
+    CopyData(usize),
+    /// Just before the first message with this tag is forwarded, e.g. `wire::COPY_DONE`.
+    Message(u8),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Fault {
+    /// Close both sides of the connection.
+    Drop(Point),
+    /// Stop forwarding frontend messages for a while, then carry on.
+    Stall(Point, Duration),
+    /// Forward exactly this many bytes of CopyData payload, cutting the
+    /// message that crosses the mark in half, then close both sides.
+    Truncate(usize),
+}
+
+/// The address of the test server, as psql itself would reach it.
+#[derive(Debug)]
+pub enum Upstream {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+static UPSTREAM: OnceCell<Upstream> = OnceCell::new();
+
+pub fn upstream() -> &'static Upstream {
+    UPSTREAM.get_or_init(|| {
+        let show = |setting: &str| {
+            let output = run_cmd("psql", &["-XAtc", &format!("SHOW {};", setting)]).unwrap();
+            assert!(output.status.success(), "could not look up {} of the test server", setting);
+            String::from_utf8(output.stdout).unwrap().trim().to_string()
+        };
+        let port = std::env::var("PGPORT").unwrap_or_else(|_| show("port"));
+        let host = std::env::var("PGHOST").unwrap_or_else(|_| show("unix_socket_directories"));
+        let host = host.split(',').next().unwrap().trim().to_string();
+        if host.starts_with('/') {
+            Upstream::Unix(PathBuf::from(host).join(format!(".s.PGSQL.{}", port)))
+        } else {
+            Upstream::Tcp(format!("{}:{}", host, port))
+        }
+    })
+}
+
+/// A connection to the test server over TCP or a Unix socket.
+pub enum Stream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Stream {
+    pub fn connect(upstream: &Upstream) -> io::Result<Self> {
+        match upstream {
+            Upstream::Tcp(addr) => TcpStream::connect(addr).map(Stream::Tcp),
+            Upstream::Unix(path) => UnixStream::connect(path).map(Stream::Unix),
+        }
+    }
+
+    pub fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Stream::Tcp(s) => s.try_clone().map(Stream::Tcp),
+            Stream::Unix(s) => s.try_clone().map(Stream::Unix),
+        }
+    }
+
+    fn shutdown_write(&self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(s) => s.shutdown(Shutdown::Write),
+            Stream::Unix(s) => s.shutdown(Shutdown::Write),
+        }
+    }
+
+    fn shutdown(&self) {
+        let _ = match self {
+            Stream::Tcp(s) => s.shutdown(Shutdown::Both),
+            Stream::Unix(s) => s.shutdown(Shutdown::Both),
+        };
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.read(buf),
+            Stream::Unix(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.write(buf),
+            Stream::Unix(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(s) => s.flush(),
+            Stream::Unix(s) => s.flush(),
+        }
+    }
+}
+
+/// A message that went through the proxy.
+#[derive(Clone, Debug)]
+pub struct Captured {
+    /// Which of psql's connections through the proxy it belongs to, from 1.
+    pub connection: usize,
+    pub sender: Sender,
+    pub message: Message,
+}
+
+struct Shared {
+    fault: Mutex<Option<Fault>>,
+    captured: Mutex<Vec<Captured>>,
+    streams: Mutex<Vec<Stream>>,
+    connections: AtomicUsize,
+    stopped: AtomicBool,
+}
+
+/// A proxy listening on 127.0.0.1. The fault, if any, only applies to the
+/// first connection, so psql's reconnect attempts go through cleanly.
+pub struct Proxy {
+    port: u16,
+    shared: Arc<Shared>,
+}
+
+impl Proxy {
+    pub fn start(fault: Option<Fault>) -> io::Result<Self> {
+        let upstream = upstream();
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let shared = Arc::new(Shared {
+            fault: Mutex::new(fault),
+            captured: Mutex::new(Vec::new()),
+            streams: Mutex::new(Vec::new()),
+            connections: AtomicUsize::new(0),
+            stopped: AtomicBool::new(false),
+        });
+        let accepting = Arc::clone(&shared);
+        thread::spawn(move || {
+            for client in listener.incoming() {
+                if accepting.stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(client) = client else { continue };
+                let shared = Arc::clone(&accepting);
+                thread::spawn(move || {
+                    let _ = serve(Stream::Tcp(client), upstream, shared);
+                });
+            }
+        });
+        Ok(Self { port, shared })
+    }
+
+    pub fn host(&self) -> &'static str {
+        "127.0.0.1"
+    }
+
+    pub fn port(&self) -> String {
+        self.port.to_string()
+    }
+
+    /// The number of connections psql has opened through the proxy so far.
+    pub fn connections(&self) -> usize {
+        self.shared.connections.load(Ordering::SeqCst)
+    }
+
+    /// Every message seen so far in either direction, in the order the proxy
+    /// read them.
+    pub fn captured(&self) -> Vec<Captured> {
+        self.shared.captured.lock().unwrap().clone()
+    }
+
+    /// The payloads of all CopyData messages psql has sent, in order.
+    pub fn copy_data(&self) -> Vec<Vec<u8>> {
+        self.captured()
+            .into_iter()
+            .filter(|c| c.sender == Sender::Frontend && c.message.tag == Some(wire::COPY_DATA))
+            .map(|c| c.message.body)
+            .collect()
+    }
+
+    /// The CopyDone or CopyFail psql ended the last COPY with, if any.
+    pub fn copy_end(&self) -> Option<Message> {
+        self.captured()
+            .into_iter()
+            .rev()
+            .filter(|c| c.sender == Sender::Frontend)
+            .map(|c| c.message)
+            .find(|m| m.tag == Some(wire::COPY_DONE) || m.tag == Some(wire::COPY_FAIL))
+    }
+
+    /// Every message seen so far, one per line, e.g. `1 F CopyDone`.
+    pub fn transcript(&self) -> String {
+        self.captured()
+            .iter()
+            .map(|c| {
+                let sender = match c.sender {
+                    Sender::Frontend => 'F',
+                    Sender::Backend => 'B',
+                };
+                format!("{} {} {}\n", c.connection, sender, c.message.describe(c.sender))
+            })
+            .collect()
+    }
+}
+
+impl Drop for Proxy {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        // Wake up the accept loop so it sees the flag.
+        let _ = TcpStream::connect(("127.0.0.1", self.port));
+        for stream in self.shared.streams.lock().unwrap().iter() {
+            stream.shutdown();
+        }
+    }
+}
+
+fn serve(client: Stream, upstream: &Upstream, shared: Arc<Shared>) -> io::Result<()> {
+    let mut fault = shared.fault.lock().unwrap().take();
+    let connection = shared.connections.fetch_add(1, Ordering::SeqCst) + 1;
+    let record = {
+        let shared = Arc::clone(&shared);
+        move |sender: Sender, message: &Message| {
+            shared.captured.lock().unwrap().push(Captured {
+                connection,
+                sender,
+                message: message.clone(),
+            })
+        }
+    };
+    let server = Stream::connect(upstream)?;
+    {
+        let mut streams = shared.streams.lock().unwrap();
+        streams.push(client.try_clone()?);
+        streams.push(server.try_clone()?);
+    }
+    let close = {
+        let (client, server) = (client.try_clone()?, server.try_clone()?);
+        move || {
+            client.shutdown();
+            server.shutdown();
+        }
+    };
+
+    let mut from_client = BufReader::new(client.try_clone()?);
+    let mut to_client = client;
+    let mut to_server = server.try_clone()?;
+
+    // Refuse SSL and GSSAPI encryption so that everything after the startup
+    // packet stays readable.
+    loop {
+        let Some(message) = Message::read_startup(&mut from_client)? else {
+            close();
+            return Ok(());
+        };
+        record(Sender::Frontend, &message);
+        match message.startup_code() {
+            Some(wire::SSL_REQUEST_CODE) | Some(wire::GSSENC_REQUEST_CODE) => to_client.write_all(b"N")?,
+            _ => {
+                to_server.write_all(&message.to_bytes())?;
+                break;
+            }
+        }
+    }
+
+    let mut from_server = BufReader::new(server);
+    let record_backend = record.clone();
+    thread::spawn(move || {
+        while let Ok(Some(message)) = Message::read(&mut from_server) {
+            record_backend(Sender::Backend, &message);
+            if to_client.write_all(&message.to_bytes()).is_err() {
+                break;
+            }
+        }
+        let _ = to_client.shutdown_write();
+    });
+
+    let mut copied = 0;
+    while let Some(message) = Message::read(&mut from_client)? {
+        record(Sender::Frontend, &message);
+        let bytes = message.to_bytes();
+        // Faults at a message tag fire before that message is forwarded.
+        match fault {
+            Some(Fault::Drop(Point::Message(tag))) if message.tag == Some(tag) => {
+                close();
+                return Ok(());
+            }
+            Some(Fault::Stall(Point::Message(tag), duration)) if message.tag == Some(tag) => {
+                fault = None;
+                thread::sleep(duration);
+            }
+            _ => {}
+        }
+        if message.tag == Some(wire::COPY_DATA) {
+            if let Some(Fault::Truncate(after)) = fault {
+                if copied + message.body.len() >= after {
+                    to_server.write_all(&bytes[..5 + after - copied])?;
+                    close();
+                    return Ok(());
+                }
+            }
+            copied += message.body.len();
+        }
+        to_server.write_all(&bytes)?;
+        // Faults at a CopyData mark fire once the message crossing it is through.
+        match fault {
+            Some(Fault::Drop(Point::CopyData(after))) if copied >= after => {
+                close();
+                return Ok(());
+            }
+            Some(Fault::Stall(Point::CopyData(after), duration)) if copied >= after => {
+                fault = None;
+                thread::sleep(duration);
+            }
+            _ => {}
+        }
+    }
+    close();
+    Ok(())
+}
@@ -0,0 +1,415 @@
+//! Machine-readable results for the cells of the test matrix. When
+//! `PSQL_TEST_REPORT_DIR` is set, every cell that finishes rewrites
+//! `results.json`, `results.xml` (JUnit) and `results.tap` there with all
+//! cells finished so far, so the files are complete once the run ends, and
+//! updates `results.html`.
+
+use crate::common::html;
+use crate::common::isolation;
+use crate::common::screen::Screen;
+use crate::common::timing::panic_message;
+use crate::common::{rerun, Profile};
+use once_cell::sync::{Lazy, OnceCell};
+use std::cell::RefCell;
+use std::env;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// One cell of the matrix: how psql gets the `\copy`, where the data comes
+/// from and its format.
+#[derive(Clone, Debug)]
+pub struct Cell {
+    pub method: &'static str,
+    pub source: &'static str,
+    pub format: &'static str,
+    /// How an interactive psql reads lines; `None` outside a terminal.
+    pub profile: Option<Profile>,
+}
+
+/// How a cell ended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Passed,
+    /// Failed at first but passed when run again. It still fails the test
+    /// unless `PSQL_TEST_FLAKY=pass` is set.
+    Flaky,
+    Failed,
+}
+
+impl Status {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Passed => "passed",
+            Self::Flaky => "flaky",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CellResult {
+    /// The libtest name, such as `terminal_stdin::text::test_psql_copy`.
+    pub test: String,
+    pub cell: Cell,
+    pub status: Status,
+    pub duration: Duration,
+    pub attempts: u32,
+    /// Why the cell failed: the error or panic message of the last attempt,
+    /// or that it passed only on a rerun.
+    pub message: Option<String>,
+    /// What `verify!` or another check found instead of the expected output.
+    pub diff: Option<String>,
+    /// The screen, step timings and raw log of the last failed PTY session,
+    /// or of every session of a cell that passed.
+    pub session_log: Option<String>,
+}
+
+impl CellResult {
+    /// Whether the cell failed its test, as a flaky one does unless
+    /// `PSQL_TEST_FLAKY=pass` is set.
+    pub fn failed(&self) -> bool {
+        self.message.is_some()
+    }
+}
+
+/// What the checks of the current attempt recorded before failing.
+#[derive(Default)]
+struct Details {
+    attempts: u32,
+    flaky: bool,
+    diff: Option<String>,
+    session_log: Option<String>,
+    /// The PTY sessions the attempt started and their raw logs.
+    sessions: Vec<(Screen, File)>,
+}
+
+thread_local! {
+    static DETAILS: RefCell<Details> = RefCell::new(Details::default());
+}
+
+static RESULTS: Lazy<Mutex<Vec<CellResult>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Records what a failed check expected and got, for the current cell.
+pub fn record_diff(diff: String) {
+    isolation::keep_artifact("diff.txt", &diff);
+    DETAILS.with(|details| details.borrow_mut().diff = Some(diff));
+}
+
+/// Records the session log of a failed PTY session, for the current cell.
+pub fn record_session_log(log: String) {
+    isolation::keep_artifact("session.log", &log);
+    DETAILS.with(|details| details.borrow_mut().session_log = Some(log));
+}
+
+/// Records a PTY session of the current cell, so its transcript can be
+/// reported even if every check passes.
+pub fn record_session(screen: Screen, log: File) {
+    DETAILS.with(|details| details.borrow_mut().sessions.push((screen, log)));
+}
+
+/// A PTY session's final screen, step timings and raw log, as reported.
+pub fn session_log(screen: &Screen, raw_log: &str) -> String {
+    format!("Screen:\n{}\nSteps:\n{}\nRaw log:\n{}", screen.dump(), screen.timing_report(), raw_log)
+}
+
+/// Records that attempt `attempt` of the current cell is starting.
+pub fn record_attempt(attempt: u32) {
+    DETAILS.with(|details| {
+        let mut details = details.borrow_mut();
+        details.attempts = attempt;
+        details.sessions.clear();
+    });
+}
+
+/// The session logs of `sessions`, read back from the start of each log.
+fn session_logs(sessions: &[(Screen, File)]) -> Option<String> {
+    if sessions.is_empty() {
+        return None;
+    }
+    let logs: Vec<String> = sessions
+        .iter()
+        .map(|(screen, log)| {
+            let mut log: &File = log;
+            let mut raw_log = Vec::new();
+            let _ = log.seek(SeekFrom::Start(0)).and_then(|_| log.read_to_end(&mut raw_log));
+            session_log(screen, &String::from_utf8_lossy(&raw_log))
+        })
+        .collect();
+    Some(logs.join("\n"))
+}
+
+/// Records that the current cell passed only on a rerun.
+pub fn record_flaky() {
+    DETAILS.with(|details| details.borrow_mut().flaky = true);
+}
+
+impl Cell {
+    pub fn new(method: &'static str, source: &'static str, format: &'static str) -> Self {
+        Self {
+            method,
+            source,
+            format,
+            profile: None,
+        }
+    }
+
+    /// A cell run in an interactive psql reading lines as `profile` does.
+    pub fn terminal(source: &'static str, format: &'static str, profile: Profile) -> Self {
+        Self {
+            method: "terminal",
+            source,
+            format,
+            profile: Some(profile),
+        }
+    }
+
+    /// Runs `test` for this cell and records the result. Terminal cells go
+    /// through [`rerun`].
+    pub fn run(self, test: impl Fn() -> Result<(), Box<dyn Error>>) -> Result<(), Box<dyn Error>> {
+        DETAILS.with(|details| *details.borrow_mut() = Details::default());
+        let start = Instant::now();
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            if self.profile.is_some() {
+                rerun(&test)
+            } else {
+                record_attempt(1);
+                test()
+            }
+        }));
+        let message = match &outcome {
+            Ok(Ok(())) => None,
+            Ok(Err(err)) => Some(err.to_string()),
+            Err(payload) => Some(panic_message(&**payload)),
+        };
+        let details = DETAILS.with(|details| details.take());
+        let status = match (&message, details.flaky) {
+            (_, true) => Status::Flaky,
+            (Some(_), false) => Status::Failed,
+            (None, false) => Status::Passed,
+        };
+        let failed = status != Status::Passed;
+        if message.is_some() {
+            isolation::mark_failed();
+        }
+        let result = CellResult {
+            test: thread::current().name().unwrap_or("unknown").to_string(),
+            cell: self,
+            status,
+            duration: start.elapsed(),
+            attempts: details.attempts,
+            message,
+            diff: details.diff.filter(|_| failed),
+            session_log: details.session_log.filter(|_| failed).or_else(|| session_logs(&details.sessions)),
+        };
+        if let Some(dir) = report_dir() {
+            let mut results = RESULTS.lock().unwrap();
+            results.push(result);
+            results.sort_by(|a, b| a.test.cmp(&b.test));
+            write_reports(&dir, &results).expect("failed to write test reports");
+        }
+        match outcome {
+            Ok(result) => result,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    /// The cell as the README's matrix names it, e.g. `terminal -n stdin text`.
+    pub fn name(&self) -> String {
+        format!("{} {}", self.row(), self.format)
+    }
+
+    /// The method and source, e.g. `terminal -n stdin`.
+    pub fn row(&self) -> String {
+        let method = match self.profile {
+            Some(Profile::NoReadline) => "terminal -n",
+            _ => self.method,
+        };
+        format!("{} {}", method, self.source)
+    }
+}
+
+fn report_dir() -> Option<PathBuf> {
+    env::var_os("PSQL_TEST_REPORT_DIR").map(PathBuf::from)
+}
+
+/// The first line of `psql --version`.
+pub fn psql_version() -> &'static str {
+    static VERSION: OnceCell<String> = OnceCell::new();
+    VERSION.get_or_init(|| {
+        Command::new("psql")
+            .arg("--version")
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            .unwrap_or_else(|_| "unknown".to_string())
+    })
+}
+
+fn write_reports(dir: &Path, results: &[CellResult]) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join("results.json"), json(results))?;
+    fs::write(dir.join("results.xml"), junit(results))?;
+    fs::write(dir.join("results.tap"), tap(results))?;
+    html::write(dir, results)
+}
+
+fn json(results: &[CellResult]) -> String {
+    let mut out = String::new();
+    writeln!(out, "{{").unwrap();
+    writeln!(out, "  \"psql_version\": {},", json_string(psql_version())).unwrap();
+    writeln!(out, "  \"cells\": [").unwrap();
+    for (i, result) in results.iter().enumerate() {
+        let optional = |value: &Option<String>| value.as_deref().map_or("null".to_string(), json_string);
+        writeln!(out, "    {{").unwrap();
+        writeln!(out, "      \"test\": {},", json_string(&result.test)).unwrap();
+        writeln!(out, "      \"method\": {},", json_string(result.cell.method)).unwrap();
+        writeln!(out, "      \"source\": {},", json_string(result.cell.source)).unwrap();
+        writeln!(out, "      \"format\": {},", json_string(result.cell.format)).unwrap();
+        writeln!(
+            out,
+            "      \"line_editor\": {},",
+            result.cell.profile.map_or("null".to_string(), |profile| json_string(profile.name()))
+        )
+        .unwrap();
+        writeln!(out, "      \"status\": {},", json_string(result.status.name())).unwrap();
+        writeln!(out, "      \"duration\": {:.3},", result.duration.as_secs_f64()).unwrap();
+        writeln!(out, "      \"attempts\": {},", result.attempts).unwrap();
+        writeln!(out, "      \"message\": {},", optional(&result.message)).unwrap();
+        writeln!(out, "      \"diff\": {},", optional(&result.diff)).unwrap();
+        writeln!(out, "      \"session_log\": {}", optional(&result.session_log)).unwrap();
+        writeln!(out, "    }}{}", if i + 1 < results.len() { "," } else { "" }).unwrap();
+    }
+    writeln!(out, "  ]").unwrap();
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn junit(results: &[CellResult]) -> String {
+    let failures = results.iter().filter(|result| result.failed()).count();
+    let time: f64 = results.iter().map(|result| result.duration.as_secs_f64()).sum();
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        out,
+        "<testsuite name=\"psql_tester\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">",
+        results.len(),
+        failures,
+        time
+    )
+    .unwrap();
+    writeln!(out, "  <properties>").unwrap();
+    writeln!(out, "    <property name=\"psql_version\" value=\"{}\"/>", xml_escape(psql_version())).unwrap();
+    writeln!(out, "  </properties>").unwrap();
+    for result in results {
+        let cell = &result.cell;
+        writeln!(
+            out,
+            "  <testcase classname=\"{}.{}\" name=\"{}\" time=\"{:.3}\">",
+            xml_escape(cell.method),
+            xml_escape(cell.source),
+            xml_escape(&result.test),
+            result.duration.as_secs_f64()
+        )
+        .unwrap();
+        writeln!(out, "    <properties>").unwrap();
+        let mut properties = vec![
+            ("method", cell.method),
+            ("source", cell.source),
+            ("format", cell.format),
+            ("status", result.status.name()),
+        ];
+        if let Some(profile) = cell.profile {
+            properties.push(("line_editor", profile.name()));
+        }
+        for (name, value) in properties {
+            writeln!(out, "      <property name=\"{}\" value=\"{}\"/>", name, xml_escape(value)).unwrap();
+        }
+        writeln!(out, "      <property name=\"attempts\" value=\"{}\"/>", result.attempts).unwrap();
+        writeln!(out, "    </properties>").unwrap();
+        if result.failed() {
+            writeln!(
+                out,
+                "    <failure message=\"{}\">{}</failure>",
+                xml_escape(result.message.as_deref().unwrap_or_default()),
+                xml_escape(result.diff.as_deref().unwrap_or_default())
+            )
+            .unwrap();
+        }
+        if let Some(log) = &result.session_log {
+            writeln!(out, "    <system-out>{}</system-out>", xml_escape(log)).unwrap();
+        }
+        writeln!(out, "  </testcase>").unwrap();
+    }
+    writeln!(out, "</testsuite>").unwrap();
+    out
+}
+
+/// Escapes text for XML, dropping the control characters XML 1.0 forbids,
+/// such as the escape sequences in raw session logs.
+pub fn xml_escape(value: &str) -> String {
+    let mut out = String::new();
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if (c as u32) < 0x20 => write!(out, "\\x{:02x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn tap(results: &[CellResult]) -> String {
+    let mut out = String::from("TAP version 13\n");
+    writeln!(out, "1..{}", results.len()).unwrap();
+    writeln!(out, "# {}", psql_version()).unwrap();
+    for (i, result) in results.iter().enumerate() {
+        let ok = if result.failed() { "not ok" } else { "ok" };
+        write!(out, "{} {} - {} ({})", ok, i + 1, result.cell.name(), result.test).unwrap();
+        if result.status == Status::Flaky {
+            write!(out, " # flaky after {} attempts", result.attempts).unwrap();
+        }
+        out.push('\n');
+        if result.failed() {
+            writeln!(out, "  ---").unwrap();
+            writeln!(out, "  duration_ms: {}", result.duration.as_millis()).unwrap();
+            writeln!(out, "  attempts: {}", result.attempts).unwrap();
+            for (name, value) in [("message", &result.message), ("diff", &result.diff), ("session_log", &result.session_log)] {
+                if let Some(value) = value {
+                    writeln!(out, "  {}: |", name).unwrap();
+                    for line in value.lines() {
+                        writeln!(out, "    {}", line.replace(|c: char| c.is_control() && c != '\t', "")).unwrap();
+                    }
+                }
+            }
+            writeln!(out, "  ...").unwrap();
+        }
+    }
+    out
+}
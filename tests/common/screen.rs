@@ -0,0 +1,291 @@
+//! A VT100 screen model fed with everything a PTY session reads, so tests can
+//! assert on what a user would see rather than on the raw byte stream with
+//! its echoed input, readline redraws and escape sequences.
+
+use crate::common::isolation::{limit, Permit};
+use crate::common::report;
+use crate::common::timing::{Step, DEFAULT_TIMEOUT};
+use expectrl::process::unix::{PtyStream, UnixProcess};
+use expectrl::process::{NonBlocking, Process};
+use expectrl::session;
+use expectrl::stream::log::LogStream;
+use expectrl::{Error, Session};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The size of the terminal ptyprocess opens.
+const ROWS: u16 = 24;
+const COLS: u16 = 80;
+
+/// How much of the most recent output failure reports show.
+const TAIL_BYTES: usize = 256;
+
+/// A PTY session whose output is also rendered on a [`Screen`].
+pub type PtySession = Session<UnixProcess, LogStream<ScreenStream, File>>;
+
+/// Spawns `command` on a PTY, logging all input and output to `log_file`.
+/// Steps wait for [`DEFAULT_TIMEOUT`] until [`set_timeout`] says otherwise.
+///
+/// ptyprocess turns terminal echo off; it is turned back on so the screen
+/// shows typed input the way a user's terminal would.
+pub fn spawn_session(command: Command, log_file: &File) -> Result<PtySession, Error> {
+    let permit = limit("pty");
+    let mut process = UnixProcess::spawn_command(command)?;
+    process.set_echo(true, None).map_err(io::Error::other)?;
+    let screen = Screen::new();
+    report::record_session(screen.clone(), log_file.try_clone()?);
+    let stream = ScreenStream {
+        stream: process.open_stream()?,
+        screen,
+        _permit: permit,
+    };
+    let mut session = session::log(Session::new(process, stream)?, log_file.try_clone()?)?;
+    set_timeout(&mut session, DEFAULT_TIMEOUT);
+    Ok(session)
+}
+
+/// Sets how long the following `expect!`, `expect_screen!` and
+/// `expect_prompt!` steps of `session` wait, usually to `suite_timeout!()`.
+/// A step known to be slow can raise it and set it back afterwards.
+pub fn set_timeout(session: &mut PtySession, timeout: Duration) {
+    session.set_expect_timeout(Some(timeout));
+    session.get_stream().screen.0.lock().unwrap().timeout = timeout;
+}
+
+/// Runs one expect step of `session` and records how long it took, for the
+/// timing report of a failure.
+pub fn step(
+    session: &mut PtySession,
+    location: &'static str,
+    description: String,
+    expect: impl FnOnce(&mut PtySession) -> bool,
+) -> bool {
+    let screen = session.get_stream().screen();
+    let (started, timeout) = {
+        let state = screen.0.lock().unwrap();
+        (state.started.elapsed(), state.timeout)
+    };
+    let start = Instant::now();
+    let matched = expect(session);
+    screen.0.lock().unwrap().steps.push(Step {
+        location,
+        description,
+        started,
+        elapsed: start.elapsed(),
+        timeout,
+        matched,
+    });
+    matched
+}
+
+/// The rendered terminal. Clones share the same screen.
+#[derive(Clone)]
+pub struct Screen(Arc<Mutex<State>>);
+
+struct State {
+    parser: vt100::Parser,
+    /// Input has been sent and no output has been read since, so the screen
+    /// may still show the prompt that input answers.
+    stale: bool,
+    timeout: Duration,
+    started: Instant,
+    /// When output was last read, and the end of everything read.
+    last_read: Option<Instant>,
+    tail: Vec<u8>,
+    steps: Vec<Step>,
+}
+
+impl Screen {
+    fn new() -> Self {
+        Self(Arc::new(Mutex::new(State {
+            parser: vt100::Parser::new(ROWS, COLS, 0),
+            stale: false,
+            timeout: DEFAULT_TIMEOUT,
+            started: Instant::now(),
+            last_read: None,
+            tail: Vec::new(),
+            steps: Vec::new(),
+        })))
+    }
+
+    fn process(&self, bytes: &[u8]) {
+        let mut state = self.0.lock().unwrap();
+        state.parser.process(bytes);
+        state.stale &= bytes.is_empty();
+        if !bytes.is_empty() {
+            state.last_read = Some(Instant::now());
+            state.tail.extend_from_slice(bytes);
+            let excess = state.tail.len().saturating_sub(TAIL_BYTES);
+            state.tail.drain(..excess);
+        }
+    }
+
+    fn input_sent(&self) {
+        self.0.lock().unwrap().stale = true;
+    }
+
+    fn is_stale(&self) -> bool {
+        self.0.lock().unwrap().stale
+    }
+
+    /// Every row of the screen, with trailing blanks removed.
+    pub fn rows(&self) -> Vec<String> {
+        let state = self.0.lock().unwrap();
+        state.parser.screen().rows(0, COLS).map(|row| row.trim_end().to_string()).collect()
+    }
+
+    /// The text of the screen, one line per row, without trailing blank rows.
+    pub fn contents(&self) -> String {
+        self.0.lock().unwrap().parser.screen().contents()
+    }
+
+    /// How long steps wait; see [`set_timeout`].
+    pub fn timeout(&self) -> Duration {
+        self.0.lock().unwrap().timeout
+    }
+
+    /// The cursor position as (row, column), both from 0.
+    pub fn cursor(&self) -> (u16, u16) {
+        self.0.lock().unwrap().parser.screen().cursor_position()
+    }
+
+    /// Whether the character at `row`, `col` is drawn in bold.
+    pub fn is_bold(&self, row: u16, col: u16) -> bool {
+        let state = self.0.lock().unwrap();
+        state.parser.screen().cell(row, col).is_some_and(|cell| cell.bold())
+    }
+
+    /// The last row containing `text`, if any.
+    pub fn find_row(&self, text: &str) -> Option<u16> {
+        self.rows().iter().rposition(|row| row.contains(text)).map(|row| row as u16)
+    }
+
+    /// The rows from `row` down to the cursor's row, one line each.
+    pub fn text_from(&self, row: u16) -> String {
+        let (cursor_row, _) = self.cursor();
+        let rows = self.rows();
+        rows[row as usize..=cursor_row as usize].iter().map(|row| format!("{}\n", row)).collect()
+    }
+
+    /// The text on the cursor's row to the left of the cursor, trailing blanks
+    /// included. While psql waits for input this is the prompt plus whatever
+    /// has been typed so far.
+    pub fn before_cursor(&self) -> String {
+        let state = self.0.lock().unwrap();
+        let screen = state.parser.screen();
+        let (row, col) = screen.cursor_position();
+        (0..col)
+            .map(|col| match screen.cell(row, col).map(|cell| cell.contents()) {
+                Some(text) if !text.is_empty() => text,
+                _ => " ".to_string(),
+            })
+            .collect()
+    }
+
+    /// The screen with numbered rows and the cursor marked, for failure logs.
+    pub fn dump(&self) -> String {
+        let (cursor_row, cursor_col) = self.cursor();
+        let rows = self.rows();
+        let last = rows.iter().rposition(|row| !row.is_empty()).unwrap_or(0).max(cursor_row as usize);
+        let mut dump = String::new();
+        for (i, row) in rows.iter().enumerate().take(last + 1) {
+            dump.push_str(&format!("{:2} |{}\n", i, row));
+        }
+        dump.push_str(&format!("cursor at row {}, column {}\n", cursor_row, cursor_col));
+        dump
+    }
+
+    /// Every step so far with its start and duration, and the last output
+    /// psql sent, for failure logs.
+    pub fn timing_report(&self) -> String {
+        let state = self.0.lock().unwrap();
+        let mut report = String::from("   start    took  step\n");
+        for step in &state.steps {
+            report.push_str(&format!("{}\n", step));
+        }
+        match state.last_read {
+            Some(last_read) => report.push_str(&format!(
+                "Last output, read {:.3}s into the session and {:.3}s ago:\n{:?}\n",
+                last_read.duration_since(state.started).as_secs_f64(),
+                last_read.elapsed().as_secs_f64(),
+                String::from_utf8_lossy(&state.tail)
+            )),
+            None => report.push_str("No output was read.\n"),
+        }
+        report
+    }
+}
+
+/// Reads from `session` until `condition` holds for the screen or `timeout`
+/// passes, and reports whether it held. Everything read is consumed, so a
+/// later `expect!` only sees output that arrives afterwards. After input has
+/// been sent the condition is only checked once some output has come back,
+/// so the prompt that input answered does not count.
+pub fn wait_for_screen(session: &mut PtySession, timeout: Duration, condition: impl Fn(&Screen) -> bool) -> bool {
+    let screen = session.get_stream().screen();
+    let start = Instant::now();
+    let mut buf = [0u8; 4096];
+    loop {
+        let eof = loop {
+            match session.try_read(&mut buf) {
+                Ok(0) => break true,
+                Ok(_) => continue,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break false,
+                Err(_) => break true,
+            }
+        };
+        if !screen.is_stale() && condition(&screen) {
+            return true;
+        }
+        if eof || start.elapsed() > timeout {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// A PTY stream that renders everything read from it on a [`Screen`].
+pub struct ScreenStream {
+    stream: PtyStream,
+    screen: Screen,
+    _permit: Permit,
+}
+
+impl ScreenStream {
+    pub fn screen(&self) -> Screen {
+        self.screen.clone()
+    }
+}
+
+impl Read for ScreenStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.stream.read(buf)?;
+        self.screen.process(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl Write for ScreenStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.screen.input_sent();
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl NonBlocking for ScreenStream {
+    fn set_non_blocking(&mut self) -> io::Result<()> {
+        self.stream.set_non_blocking()
+    }
+
+    fn set_blocking(&mut self) -> io::Result<()> {
+        self.stream.set_blocking()
+    }
+}
@@ -0,0 +1,121 @@
+//! How long PTY sessions wait for psql, a record of how long each expect step
+//! took, and reruns that tell a flaky interactive test from a broken one.
+
+use crate::common::report;
+use std::any::Any;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+/// How long a step waits when neither `PSQL_TEST_TIMEOUT` nor the suite's own
+/// variable is set.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The step timeout for the suite of `module`, a `module_path!()` such as
+/// `integration::cancel::terminal_file`. It is taken, in seconds, from
+/// `PSQL_TEST_TIMEOUT_CANCEL` for that suite, then from `PSQL_TEST_TIMEOUT`,
+/// and is otherwise [`DEFAULT_TIMEOUT`].
+pub fn suite_timeout(module: &str) -> Duration {
+    let suite = module.split("::").nth(1).unwrap_or_default().to_uppercase();
+    seconds_var(&format!("PSQL_TEST_TIMEOUT_{}", suite))
+        .or_else(|| seconds_var("PSQL_TEST_TIMEOUT"))
+        .unwrap_or(DEFAULT_TIMEOUT)
+}
+
+fn seconds_var(name: &str) -> Option<Duration> {
+    let value = env::var(name).ok()?;
+    let seconds: f64 = value
+        .parse()
+        .unwrap_or_else(|_| panic!("{} must be a number of seconds, not {:?}", name, value));
+    Some(Duration::from_secs_f64(seconds))
+}
+
+/// The step timeout for the calling test's suite; see [`suite_timeout`].
+#[macro_export]
+macro_rules! suite_timeout {
+    () => {
+        $crate::common::timing::suite_timeout(module_path!())
+    };
+}
+
+/// One `expect!`, `expect_screen!` or `expect_prompt!` of a session.
+#[derive(Clone, Debug)]
+pub struct Step {
+    /// Where in the test the step is, as `file:line`.
+    pub location: &'static str,
+    pub description: String,
+    /// When the step started, counted from the start of the session.
+    pub started: Duration,
+    pub elapsed: Duration,
+    pub timeout: Duration,
+    pub matched: bool,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>8.3}s {:>7.3}s  {}  {}",
+            self.started.as_secs_f64(),
+            self.elapsed.as_secs_f64(),
+            self.location,
+            self.description
+        )?;
+        if !self.matched {
+            write!(f, "  FAILED after the {:.3}s timeout", self.timeout.as_secs_f64())?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs an interactive test and, if it fails, runs it again up to
+/// `PSQL_TEST_RERUNS` times (none by default). A test that passes on a rerun
+/// is reported as flaky and still fails, unless `PSQL_TEST_FLAKY=pass` is
+/// set; one that fails every attempt is reported as deterministic.
+pub fn rerun(test: impl Fn() -> Result<(), Box<dyn Error>>) -> Result<(), Box<dyn Error>> {
+    let reruns: u32 = match env::var("PSQL_TEST_RERUNS") {
+        Ok(value) => value.parse().unwrap_or_else(|_| panic!("PSQL_TEST_RERUNS must be a count, not {:?}", value)),
+        Err(_) => 0,
+    };
+    let attempts = reruns + 1;
+    let mut failures = Vec::new();
+    for attempt in 1..=attempts {
+        report::record_attempt(attempt);
+        let failure = match panic::catch_unwind(AssertUnwindSafe(&test)) {
+            Ok(Ok(())) => None,
+            Ok(Err(err)) => Some(err.to_string()),
+            Err(payload) => Some(panic_message(&*payload)),
+        };
+        match failure {
+            None if failures.is_empty() => return Ok(()),
+            None => {
+                let message = format!("passed on attempt {} of {} after failing with: {}", attempt, attempts, failures.join("; "));
+                println!("FLAKY: {}", message);
+                report::record_flaky();
+                if env::var("PSQL_TEST_FLAKY").is_ok_and(|value| value == "pass") {
+                    return Ok(());
+                }
+                return Err(format!("Flaky test {}", message).into());
+            }
+            Some(failure) => {
+                println!("Attempt {} of {} failed: {}", attempt, attempts, failure);
+                failures.push(failure);
+            }
+        }
+    }
+    println!("DETERMINISTIC: failed all {} attempts", attempts);
+    Err(failures.pop().unwrap().into())
+}
+
+/// The message a panic was started with.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "panic".to_string()
+    }
+}
@@ -0,0 +1,204 @@
+//! Message framing for the PostgreSQL frontend/backend protocol, version 3.
+
+use std::io::{self, Read};
+
+pub const CANCEL_REQUEST_CODE: u32 = 80877102;
+pub const SSL_REQUEST_CODE: u32 = 80877103;
+pub const GSSENC_REQUEST_CODE: u32 = 80877104;
+
+pub const QUERY: u8 = b'Q';
+pub const TERMINATE: u8 = b'X';
+pub const COPY_DATA: u8 = b'd';
+pub const COPY_DONE: u8 = b'c';
+pub const COPY_FAIL: u8 = b'f';
+pub const COMMAND_COMPLETE: u8 = b'C';
+pub const ERROR_RESPONSE: u8 = b'E';
+
+/// Which side of the connection sent a message. A few tags mean different
+/// things depending on the direction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sender {
+    Frontend,
+    Backend,
+}
+
+/// One message as it appears on the wire. Messages sent before the startup
+/// packet has been accepted (StartupMessage, SSLRequest, ...) have no tag.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub tag: Option<u8>,
+    pub body: Vec<u8>,
+}
+
+impl Message {
+    pub fn new(tag: u8, body: Vec<u8>) -> Self {
+        Self {
+            tag: Some(tag),
+            body,
+        }
+    }
+
+    /// Reads an untagged startup-phase message, or `None` on a clean EOF.
+    pub fn read_startup(reader: &mut impl Read) -> io::Result<Option<Self>> {
+        let Some(len) = read_len(reader)? else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            tag: None,
+            body: read_body(reader, len)?,
+        }))
+    }
+
+    /// Reads a tagged message, or `None` on a clean EOF.
+    pub fn read(reader: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut tag = [0u8; 1];
+        if reader.read(&mut tag)? == 0 {
+            return Ok(None);
+        }
+        let len = read_len(reader)?.ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        Ok(Some(Self::new(tag[0], read_body(reader, len)?)))
+    }
+
+    /// The request code of an untagged message: the protocol version of a
+    /// StartupMessage, or one of the `*_REQUEST_CODE`s.
+    pub fn startup_code(&self) -> Option<u32> {
+        match (self.tag, self.body.get(..4)) {
+            (None, Some(code)) => Some(u32::from_be_bytes(code.try_into().unwrap())),
+            _ => None,
+        }
+    }
+
+    /// The message type's name in the protocol documentation.
+    pub fn name(&self, sender: Sender) -> &'static str {
+        match (sender, self.tag) {
+            (_, None) => match self.startup_code() {
+                Some(CANCEL_REQUEST_CODE) => "CancelRequest",
+                Some(SSL_REQUEST_CODE) => "SSLRequest",
+                Some(GSSENC_REQUEST_CODE) => "GSSENCRequest",
+                _ => "StartupMessage",
+            },
+            (_, Some(COPY_DATA)) => "CopyData",
+            (_, Some(COPY_DONE)) => "CopyDone",
+            (Sender::Frontend, Some(tag)) => match tag {
+                QUERY => "Query",
+                COPY_FAIL => "CopyFail",
+                TERMINATE => "Terminate",
+                b'p' => "PasswordMessage",
+                b'P' => "Parse",
+                b'B' => "Bind",
+                b'D' => "Describe",
+                b'E' => "Execute",
+                b'S' => "Sync",
+                b'H' => "Flush",
+                b'C' => "Close",
+                _ => "Unknown",
+            },
+            (Sender::Backend, Some(tag)) => match tag {
+                b'R' => "Authentication",
+                b'S' => "ParameterStatus",
+                b'K' => "BackendKeyData",
+                b'Z' => "ReadyForQuery",
+                b'T' => "RowDescription",
+                b'D' => "DataRow",
+                COMMAND_COMPLETE => "CommandComplete",
+                b'G' => "CopyInResponse",
+                b'H' => "CopyOutResponse",
+                b'W' => "CopyBothResponse",
+                ERROR_RESPONSE => "ErrorResponse",
+                b'N' => "NoticeResponse",
+                b'I' => "EmptyQueryResponse",
+                b'A' => "NotificationResponse",
+                b'1' => "ParseComplete",
+                b'2' => "BindComplete",
+                b'3' => "CloseComplete",
+                b'n' => "NoData",
+                b't' => "ParameterDescription",
+                _ => "Unknown",
+            },
+        }
+    }
+
+    /// A one-line rendering for logs, e.g. `CopyData "1\t2\n"`.
+    pub fn describe(&self, sender: Sender) -> String {
+        let name = self.name(sender);
+        let details = match (sender, self.tag) {
+            (Sender::Frontend, Some(QUERY)) | (Sender::Frontend, Some(COPY_FAIL)) => {
+                format!("{:?}", cstring(&self.body))
+            }
+            (Sender::Backend, Some(COMMAND_COMPLETE)) => format!("{:?}", cstring(&self.body)),
+            (Sender::Backend, Some(ERROR_RESPONSE)) | (Sender::Backend, Some(b'N')) => {
+                let fields = error_fields(&self.body);
+                let field = |code: u8| fields.iter().find(|(c, _)| *c == code).map_or("", |(_, v)| v.as_str());
+                format!("{} {} {:?}", field(b'S'), field(b'C'), field(b'M'))
+            }
+            (Sender::Backend, Some(b'S')) => {
+                let mut parts = self.body.split(|&b| b == 0);
+                let name = String::from_utf8_lossy(parts.next().unwrap_or_default());
+                let value = String::from_utf8_lossy(parts.next().unwrap_or_default());
+                format!("{}={:?}", name, value)
+            }
+            (Sender::Backend, Some(b'Z')) => format!("{}", self.body.first().map_or('?', |&b| b as char)),
+            (_, Some(COPY_DATA)) => {
+                const SHOWN: usize = 64;
+                let shown = &self.body[..self.body.len().min(SHOWN)];
+                let mut details = format!("{:?}", String::from_utf8_lossy(shown));
+                if self.body.len() > SHOWN {
+                    details.push_str(&format!("... ({} bytes)", self.body.len()));
+                }
+                details
+            }
+            _ if self.body.is_empty() => String::new(),
+            _ => format!("({} bytes)", self.body.len()),
+        };
+        if details.is_empty() {
+            name.to_string()
+        } else {
+            format!("{} {}", name, details)
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.body.len() + 5);
+        bytes.extend(self.tag);
+        bytes.extend_from_slice(&(self.body.len() as u32 + 4).to_be_bytes());
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+}
+
+fn read_len(reader: &mut impl Read) -> io::Result<Option<usize>> {
+    let mut len = [0u8; 4];
+    let mut filled = 0;
+    while filled < len.len() {
+        match reader.read(&mut len[filled..])? {
+            0 if filled == 0 => return Ok(None),
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            n => filled += n,
+        }
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len < 4 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid message length {}", len)));
+    }
+    Ok(Some(len - 4))
+}
+
+fn read_body(reader: &mut impl Read, len: usize) -> io::Result<Vec<u8>> {
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body)?;
+    Ok(body)
+}
+
+/// The text of a NUL-terminated string at the start of `body`.
+pub fn cstring(body: &[u8]) -> String {
+    let end = body.iter().position(|&b| b == 0).unwrap_or(body.len());
+    String::from_utf8_lossy(&body[..end]).into_owned()
+}
+
+/// The fields of an ErrorResponse or NoticeResponse, as (code, value) pairs.
+pub fn error_fields(body: &[u8]) -> Vec<(u8, String)> {
+    body.split(|&b| b == 0)
+        .filter(|field| !field.is_empty())
+        .map(|field| (field[0], String::from_utf8_lossy(&field[1..]).into_owned()))
+        .collect()
+}
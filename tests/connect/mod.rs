@@ -0,0 +1,327 @@
+//! Connecting to the harness's private cluster without the environment the
+//! rest of the tests rely on: with options, URIs, key=value strings, a
+//! service file and `PG*` variables, over its socket and over TCP. Each way
+//! should give the same `\conninfo`, `\copy` and prompts, apart from the
+//! socket or host it names. The tests also cover lists of hosts with
+//! `target_session_attrs` and what `\connect` takes over from the previous
+//! connection.
+
+mod terminal_tty;
+
+use crate::common::cluster::{Cluster, DATABASE, SUPERUSER};
+use crate::common::*;
+use std::error::Error;
+use std::fs;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::Command;
+use uuid::Uuid;
+
+/// Where a connection goes, as `\conninfo` puts it.
+#[derive(Clone, Copy)]
+enum Host {
+    Socket,
+    /// TCP to `host`.
+    Name,
+    /// TCP to `hostaddr` alone.
+    Address,
+}
+
+/// One way of connecting as the cluster's superuser.
+struct Way {
+    name: &'static str,
+    command: Command,
+    host: Host,
+}
+
+/// A service file with `psql_tester` for the socket and `psql_tester_tcp`
+/// for TCP.
+fn service_file(cluster: &Cluster) -> Result<PathBuf, Box<dyn Error>> {
+    let path = test_dir().join("pg_service.conf");
+    fs::write(
+        &path,
+        format!(
+            "# The cluster's superuser\n[psql_tester]\nhost={dir}\nport={port}\nuser={user}\ndbname={db}\n\n\
+             [psql_tester_tcp]\nhost=127.0.0.1\nport={port}\nuser={user}\ndbname={db}\n",
+            dir = cluster.data_dir().display(),
+            port = cluster.port,
+            user = SUPERUSER,
+            db = DATABASE
+        ),
+    )?;
+    Ok(path)
+}
+
+fn ways(cluster: &Cluster) -> Result<Vec<Way>, Box<dyn Error>> {
+    let dir = cluster.data_dir().display().to_string();
+    let port = cluster.port;
+    let service_file = service_file(cluster)?;
+    let conninfo = |conninfo: String| {
+        let mut command = cluster.psql_defaults();
+        command.args(["-d", &conninfo]);
+        command
+    };
+    let mut options = cluster.psql_defaults();
+    options.args(["-h", &dir, "-p", &port.to_string(), "-U", SUPERUSER, DATABASE]);
+    let mut environment = cluster.psql_defaults();
+    environment
+        .env("PGHOST", "127.0.0.1")
+        .env("PGPORT", port.to_string())
+        .env("PGUSER", SUPERUSER)
+        .env("PGDATABASE", DATABASE);
+    let mut service = conninfo("service=psql_tester".to_string());
+    service.env("PGSERVICEFILE", &service_file);
+    let mut service_variable = cluster.psql_defaults();
+    service_variable
+        .env("PGSERVICEFILE", &service_file)
+        .env("PGSERVICE", "psql_tester_tcp");
+
+    Ok(vec![
+        Way {
+            name: "options",
+            command: options,
+            host: Host::Socket,
+        },
+        Way {
+            name: "URI with the socket as host",
+            command: conninfo(format!("postgresql://{}@{}:{}/{}", SUPERUSER, dir.replace('/', "%2F"), port, DATABASE)),
+            host: Host::Socket,
+        },
+        Way {
+            name: "URI with the socket as parameter",
+            command: conninfo(format!("postgresql:///{}?host={}&port={}&user={}", DATABASE, dir, port, SUPERUSER)),
+            host: Host::Socket,
+        },
+        Way {
+            name: "URI over TCP",
+            command: conninfo(format!("postgres://{}@127.0.0.1:{}/{}", SUPERUSER, port, DATABASE)),
+            host: Host::Name,
+        },
+        Way {
+            name: "key=value with the socket",
+            command: conninfo(format!("host='{}' port={} user={} dbname={}", dir, port, SUPERUSER, DATABASE)),
+            host: Host::Socket,
+        },
+        Way {
+            name: "key=value over TCP",
+            command: conninfo(format!("hostaddr=127.0.0.1 port={} user={} dbname={}", port, SUPERUSER, DATABASE)),
+            host: Host::Address,
+        },
+        Way {
+            name: "service",
+            command: service,
+            host: Host::Socket,
+        },
+        Way {
+            name: "PGSERVICE",
+            command: service_variable,
+            host: Host::Name,
+        },
+        Way {
+            name: "environment",
+            command: environment,
+            host: Host::Name,
+        },
+    ])
+}
+
+/// What `\conninfo` says about a connection to `database` on the cluster.
+fn conninfo(cluster: &Cluster, database: &str, user: &str, host: Host) -> String {
+    let place = match host {
+        Host::Socket => format!("via socket in \"{}\"", cluster.data_dir().display()),
+        Host::Name => "on host \"127.0.0.1\"".to_string(),
+        Host::Address => "on address \"127.0.0.1\"".to_string(),
+    };
+    format!(
+        "You are connected to database \"{}\" as user \"{}\" {} at port \"{}\".\n",
+        database, user, place, cluster.port
+    )
+}
+
+/// A port nothing listens on.
+fn closed_port() -> Result<u16, Box<dyn Error>> {
+    Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
+
+#[test]
+fn test_connect_conninfo() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    for mut way in ways(cluster)? {
+        println!("Connecting with {}", way.name);
+        let output = way.command.args(["-c", "\\conninfo"]).output()?;
+        expect_exit_status!(output, 0);
+        verify!(&output.stdout, &conninfo(cluster, DATABASE, SUPERUSER, way.host));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_connect_copy() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    let table = Uuid::new_v4();
+    let output = cluster.query(&format!("CREATE TABLE \"{}\" (a int8, b int8);", table))?;
+    expect_exit_status!(output, 0);
+    let env = get_test_environment();
+    for mut way in ways(cluster)? {
+        println!("Connecting with {}", way.name);
+        let output = way
+            .command
+            .args(["-v", "ON_ERROR_STOP=1"])
+            .args(["-c", &format!("\\copy \"{}\" from '{}'", table, env.file_path_text)])
+            .args(["-c", &format!("\\copy \"{}\" to stdout", table)])
+            .args(["-c", &format!("TRUNCATE \"{}\";", table)])
+            .output()?;
+        expect_exit_status!(output, 0);
+        verify!(&output.stdout, "COPY 2\n1\t2\n3\t4\nTRUNCATE TABLE\n");
+    }
+    let output = cluster.query(&format!("DROP TABLE \"{}\";", table))?;
+    expect_exit_status!(output, 0);
+    Ok(())
+}
+
+/// Settings given alongside a service override the service's.
+#[test]
+fn test_connect_service_override() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    let service_file = service_file(cluster)?;
+    let output = cluster
+        .psql_defaults()
+        .env("PGSERVICEFILE", &service_file)
+        .args(["-d", "service=psql_tester dbname=template1", "-c", "\\conninfo"])
+        .output()?;
+    expect_exit_status!(output, 0);
+    verify!(&output.stdout, &conninfo(cluster, "template1", SUPERUSER, Host::Socket));
+
+    let output = cluster
+        .psql_defaults()
+        .env("PGSERVICEFILE", &service_file)
+        .env("PGSERVICE", "psql_tester")
+        .args(["-h", "127.0.0.1", "-c", "\\conninfo"])
+        .output()?;
+    expect_exit_status!(output, 0);
+    verify!(&output.stdout, &conninfo(cluster, DATABASE, SUPERUSER, Host::Name));
+    Ok(())
+}
+
+#[test]
+fn test_connect_service_missing() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    let output = cluster
+        .psql_defaults()
+        .env("PGSERVICEFILE", service_file(cluster)?)
+        .args(["-d", "service=missing", "-c", "\\conninfo"])
+        .output()?;
+    expect_exit_status!(output, 2);
+    verify!(&output.stderr, "psql: error: definition of service \"missing\" not found\n");
+    Ok(())
+}
+
+/// psql tries each host in turn, skipping one that is down and, with
+/// `target_session_attrs`, a server of the wrong kind. The cluster is a
+/// primary, so asking for a standby fails.
+#[test]
+fn test_connect_multiple_hosts() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    let closed = closed_port()?;
+    for (target_session_attrs, error) in [
+        ("any", None),
+        ("read-write", None),
+        ("primary", None),
+        ("prefer-standby", None),
+        ("read-only", Some("session is not read-only")),
+        ("standby", Some("server is not in hot standby mode")),
+    ] {
+        for uri in [false, true] {
+            let connection = if uri {
+                format!(
+                    "postgresql://{user}@127.0.0.1:{closed},127.0.0.1:{port}/{db}?target_session_attrs={attrs}",
+                    user = SUPERUSER,
+                    closed = closed,
+                    port = cluster.port,
+                    db = DATABASE,
+                    attrs = target_session_attrs
+                )
+            } else {
+                format!(
+                    "host=127.0.0.1,127.0.0.1 port={},{} user={} dbname={} target_session_attrs={}",
+                    closed, cluster.port, SUPERUSER, DATABASE, target_session_attrs
+                )
+            };
+            println!("Connecting with {}", connection);
+            let output = cluster.psql_defaults().args(["-d", &connection, "-c", "\\conninfo"]).output()?;
+            match error {
+                None => {
+                    expect_exit_status!(output, 0);
+                    verify!(&output.stdout, &conninfo(cluster, DATABASE, SUPERUSER, Host::Name));
+                }
+                Some(error) => {
+                    expect_exit_status!(output, 2);
+                    verify_contains!(
+                        &output.stderr,
+                        &format!("connection to server at \"127.0.0.1\", port {} failed: Connection refused", closed)
+                    );
+                    verify_contains!(
+                        &output.stderr,
+                        &format!("connection to server at \"127.0.0.1\", port {} failed: {}", cluster.port, error)
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `\connect` with positional arguments takes what it isn't given from the
+/// previous connection, however that was made. With a key=value string it
+/// takes nothing unless told to, so libpq's defaults apply, here a closed
+/// port. A script ends with status 2 when its `\connect` fails.
+#[test]
+fn test_connect_reuse() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    let closed = closed_port()?.to_string();
+    let script = test_dir().join("script.sql");
+    fs::write(
+        &script,
+        "\\c template1\n\\conninfo\n\
+         \\c - auth_trust\n\\conninfo\n\
+         \\c -reuse-previous=on \"dbname=postgres\"\n\\conninfo\n\
+         \\c \"dbname=template1\"\n\\conninfo\n",
+    )?;
+    // Settings from the environment would be defaults for the new connection
+    // too.
+    let ways = ways(cluster)?.into_iter().filter(|way| !["environment", "PGSERVICE"].contains(&way.name));
+    for mut way in ways {
+        println!("Connecting with {}", way.name);
+        // `\connect` takes over hostaddr but not an unset host, which then
+        // comes from PGHOST.
+        let host = match way.host {
+            Host::Address => Host::Name,
+            host => host,
+        };
+        let output = way
+            .command
+            .env("PGHOST", "127.0.0.1")
+            .env("PGPORT", &closed)
+            .arg("-f")
+            .arg(&script)
+            .output()?;
+        expect_exit_status!(output, 2);
+        verify!(
+            &output.stdout,
+            &format!(
+                "You are now connected to database \"template1\" as user \"{su}\".\n{}\
+                 You are now connected to database \"template1\" as user \"auth_trust\".\n{}\
+                 You are now connected to database \"postgres\" as user \"auth_trust\".\n{}",
+                conninfo(cluster, "template1", SUPERUSER, host),
+                conninfo(cluster, "template1", "auth_trust", host),
+                conninfo(cluster, DATABASE, "auth_trust", host),
+                su = SUPERUSER
+            )
+        );
+        verify_contains!(
+            &output.stderr,
+            &format!("\\connect: connection to server at \"127.0.0.1\", port {} failed: Connection refused", closed)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,43 @@
+//! Prompts of interactive sessions connected each way. `%M`, `%>`, `%n` and
+//! `%/` show the host, port, user and database of the connection, whichever
+//! way it was made, and follow `\connect`. A `\connect` that fails keeps the
+//! previous connection.
+
+use super::{closed_port, ways, Host};
+use crate::common::cluster::{Cluster, DATABASE, SUPERUSER};
+use crate::common::*;
+use expectrl::Eof;
+use std::error::Error;
+use tempfile::NamedTempFile;
+
+const PROMPT: &str = "%M:%>:%n@%/%# ";
+
+#[test]
+fn test_connect_prompt() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    let closed = closed_port()?.to_string();
+    for mut way in ways(cluster)? {
+        println!("Connecting with {}", way.name);
+        let host = match way.host {
+            Host::Socket => format!("[local:{}]", cluster.data_dir().display()),
+            Host::Name => "127.0.0.1".to_string(),
+            // Without a host name, %M takes the connection for a local one.
+            Host::Address => "[local]".to_string(),
+        };
+        let prompt = |database: &str| format!("{}:{}:{}@{}# ", host, cluster.port, SUPERUSER, database);
+        let temp_file = NamedTempFile::new()?;
+        way.command.arg("-v").arg(format!("PROMPT1={}", PROMPT));
+        let mut session = spawn_session(way.command, temp_file.as_file())?;
+        set_timeout(&mut session, suite_timeout!());
+
+        expect_prompt!(&mut session, &prompt(DATABASE), &temp_file);
+        session.send_line("\\c template1")?;
+        expect_prompt!(&mut session, &prompt("template1"), &temp_file);
+        session.send_line(format!("\\c \"port={} dbname={}\"", closed, DATABASE))?;
+        expect_screen!(&mut session, "Previous connection kept", &temp_file);
+        expect_prompt!(&mut session, &prompt("template1"), &temp_file);
+        session.send_line("\\q")?;
+        session.expect(Eof)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use crate::common::proxy::{Fault, Point, Proxy};
+use crate::common::*;
+use std::error::Error;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+#[test]
+fn test_psql_copy_connection_dropped() -> Result<(), Box<dyn Error>> {
+    let test_table = Uuid::new_v4();
+    let output = run_cmd("psql", &["-c", &format!(r#"CREATE TABLE "{}" (c1 int8, c2 int8);"#, test_table)])?;
+    expect_create_table!(output);
+    let large_file = test_dir().join(format!("{}.text", test_table));
+    write_series_file(&large_file, 100000)?;
+
+    let proxy = Proxy::start(Some(Fault::Drop(Point::CopyData(65536))))?;
+    let output = run_cmd("psql", &["-h", proxy.host(), "-p", &proxy.port(), "-c", &format!(r#"\copy "{}" from '{}'"#, test_table, large_file.display())])?;
+    expect_exit_status!(output, 2);
+    verify_contains!(output.stderr, "server closed the connection unexpectedly");
+    verify_contains!(output.stderr, "connection to server was lost");
+    assert_eq!(proxy.connections(), 1);
+
+    let output = run_cmd("psql", &["-XAtc", &format!(r#"SELECT count(*) FROM "{}";"#, test_table)])?;
+    verify!(output.stdout, "\n0\n");
+    let output = run_cmd("psql", &["-c", &format!(r#"DROP TABLE "{}";"#, test_table)])?;
+    expect_drop_table!(output);
+    Ok(())
+}
+
+#[test]
+fn test_psql_copy_connection_stalled() -> Result<(), Box<dyn Error>> {
+    let test_table = Uuid::new_v4();
+    let output = run_cmd("psql", &["-c", &format!(r#"CREATE TABLE "{}" (c1 int8, c2 int8);"#, test_table)])?;
+    expect_create_table!(output);
+    let large_file = test_dir().join(format!("{}.text", test_table));
+    write_series_file(&large_file, 100000)?;
+
+    let stall = Duration::from_secs(2);
+    let proxy = Proxy::start(Some(Fault::Stall(Point::CopyData(65536), stall)))?;
+    let started = Instant::now();
+    let output = run_cmd("psql", &["-h", proxy.host(), "-p", &proxy.port(), "-c", &format!(r#"\copy "{}" from '{}'"#, test_table, large_file.display())])?;
+    expect_exit_status!(output, 0);
+    verify!(output.stdout, "\nCOPY 100000\n");
+    assert!(started.elapsed() >= stall, "psql finished the COPY during the stall");
+
+    let output = run_cmd("psql", &["-c", &format!(r#"DROP TABLE "{}";"#, test_table)])?;
+    expect_drop_table!(output);
+    Ok(())
+}
@@ -0,0 +1,3 @@
+mod command_file;
+mod script_stdin;
+mod terminal_file;
@@ -0,0 +1,35 @@
+use crate::common::proxy::{Fault, Proxy};
+use crate::common::*;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Write;
+use uuid::Uuid;
+
+#[test]
+fn test_psql_copy_connection_truncated() -> Result<(), Box<dyn Error>> {
+    let test_table = Uuid::new_v4();
+    let output = run_cmd("psql", &["-c", &format!(r#"CREATE TABLE "{}" (c1 int8, c2 int8);"#, test_table)])?;
+    expect_create_table!(output);
+    let large_file = test_dir().join(format!("{}.text", test_table));
+    write_series_file(&large_file, 100000)?;
+
+    let test_file_path = test_dir().join(format!("{}", test_table));
+    let mut test_file = File::create(&test_file_path)?;
+    writeln!(test_file, r#"\copy "{}" from stdin"#, test_table)?;
+    test_file.write_all(&fs::read(&large_file)?)?;
+    writeln!(test_file, r"\.")?;
+    writeln!(test_file, "SELECT 1;")?;
+
+    // Cut the connection in the middle of a CopyData message.
+    let proxy = Proxy::start(Some(Fault::Truncate(65536 + 7)))?;
+    let output = run_cmd("psql", &["-h", proxy.host(), "-p", &proxy.port(), "-f", &test_file_path.to_string_lossy()])?;
+    expect_exit_status!(output, 2);
+    verify_contains!(output.stderr, "connection to server was lost");
+    assert_eq!(proxy.connections(), 1);
+
+    let output = run_cmd("psql", &["-XAtc", &format!(r#"SELECT count(*) FROM "{}";"#, test_table)])?;
+    verify!(output.stdout, "\n0\n");
+    let output = run_cmd("psql", &["-c", &format!(r#"DROP TABLE "{}";"#, test_table)])?;
+    expect_drop_table!(output);
+    Ok(())
+}
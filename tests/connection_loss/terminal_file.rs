@@ -0,0 +1,48 @@
+use crate::common::proxy::{Fault, Point, Proxy};
+use crate::common::wire;
+use crate::common::*;
+use expectrl::Eof;
+use std::error::Error;
+use uuid::Uuid;
+
+fn connection_reset() -> Result<(), Box<dyn Error>> {
+    let env = get_test_environment();
+    let test_table = Uuid::new_v4();
+    let output = run_cmd("psql", &["-c", &format!(r#"CREATE TABLE "{}" (c1 int8, c2 int8);"#, test_table)])?;
+    expect_create_table!(output);
+
+    // Let all of the data through, but lose the connection before CopyDone.
+    let proxy = Proxy::start(Some(Fault::Drop(Point::Message(wire::COPY_DONE))))?;
+
+    let temp_file = tempfile::NamedTempFile::new()?;
+    let log_file = temp_file.as_file();
+
+    let mut command = psql_command();
+    command.args(["-h", proxy.host(), "-p", &proxy.port()]);
+    let mut session = spawn_session(command, log_file)?;
+
+    set_timeout(&mut session, suite_timeout!());
+
+    expect_prompt!(&mut session, PROMPT1, &temp_file, proxy);
+    session.send_line(format!(r#"\copy "{}" from '{}'"#, test_table, env.file_path_text))?;
+    expect!(&mut session, "The connection to the server was lost. Attempting reset: ", &temp_file, proxy);
+    expect!(&mut session, "Succeeded.", &temp_file, proxy);
+    expect_prompt!(&mut session, PROMPT1, &temp_file, proxy);
+    session.send_line(format!(r#"\copy "{}" from '{}'"#, test_table, env.file_path_text))?;
+    expect!(&mut session, "COPY 2", &temp_file, proxy);
+    expect_prompt!(&mut session, PROMPT1, &temp_file, proxy);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+    assert_eq!(proxy.connections(), 2);
+
+    let output = run_cmd("psql", &["-c", &format!(r#"SELECT * FROM "{}";"#, test_table)])?;
+    expect_result_set!(output);
+    let output = run_cmd("psql", &["-c", &format!(r#"DROP TABLE "{}";"#, test_table)])?;
+    expect_drop_table!(output);
+    Ok(())
+}
+
+#[test]
+fn test_psql_copy_connection_reset() -> Result<(), Box<dyn Error>> {
+    rerun(connection_reset)
+}
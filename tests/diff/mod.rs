@@ -0,0 +1,73 @@
+//! The diff `verify!` prints, as recorded for reports.
+
+use crate::common::diff::render_diff;
+
+#[test]
+fn test_diff_trailing_space() {
+    let diff = render_diff(b" c1 | c2\n----+----\n", b" c1 | c2 \n----+----\n").plain;
+    assert_eq!(diff, "-·c1·|·c2⏎\n+·c1·|·c2{+·+}⏎\n ----+----\n");
+}
+
+#[test]
+fn test_diff_tab() {
+    let diff = render_diff(b"1 2\n", b"1\t2\n").plain;
+    assert_eq!(diff, "-1[-·-]2⏎\n+1{+→+}2⏎\n");
+}
+
+#[test]
+fn test_diff_words() {
+    let diff = render_diff(b"INSERT 0 2\n", b"COPY 2\n").plain;
+    assert_eq!(diff, "-[-INSERT·0-]·2⏎\n+{+COPY+}·2⏎\n");
+}
+
+#[test]
+fn test_diff_lines() {
+    let diff = render_diff(b"COPY 2\n", b"COPY 2\nCOPY 1\n").plain;
+    assert_eq!(diff, " COPY 2\n+COPY·1⏎\n");
+}
+
+#[test]
+fn test_diff_binary() {
+    let diff = render_diff(b"\0\0\0\x02COPY\0", b"\0\0\0\x01COPY\0").plain;
+    verify!(
+        diff,
+        r#"
+ Binary output, shown as a hex dump:
+-00000000  00 00 00 0[-2-] 43 4f 50 59 00                       |....COPY.|
++00000000  00 00 00 0{+1+} 43 4f 50 59 00                       |....COPY.|
+"#
+    );
+}
+
+#[test]
+fn test_diff_pgcopy() {
+    let file = |field: &[u8]| {
+        let mut bytes = b"PGCOPY\n\xff\r\n\0\0\0\0\0\0\0\0\0\0\x01".to_vec();
+        bytes.extend_from_slice(&(field.len() as i32).to_be_bytes());
+        bytes.extend_from_slice(field);
+        bytes.extend_from_slice(b"\xff\xff");
+        bytes
+    };
+    let diff = render_diff(&file(&[0, 0, 0, 1]), &file(&[0, 0, 0, 0, 0, 0, 0, 1])).plain;
+    verify!(
+        diff,
+        r#"
+ Binary COPY structure, actual vs expected:
+!tuple 1 field 1 length 4 vs 8
+ Binary output, shown as a hex dump:
+ 00000000  50 47 43 4f 50 59 0a ff 0d 0a 00 00 00 00 00 00  |PGCOPY..........|
+-00000010  00 00 00 00 01 00 00 00 0[-4-] 00 00 00 0[-1 ff ff-]     |...............|
++00000010  00 00 00 00 01 00 00 00 0{+8+} 00 00 00 0{+0+} {+00+} {+00+} {+00+}  |...............{+.+}|
++00000020  01 ff ff                                         |...|
+"#
+    );
+}
+
+#[test]
+fn test_diff_colored() {
+    let diff = render_diff(b"1 2\n", b"1\t2\n").colored;
+    // Red and green lines, with the changed text also bold and underlined.
+    assert!(diff.contains("\x1b[0m\x1b[31m-"), "{:?}", diff);
+    assert!(diff.contains("\x1b[0m\x1b[32m+"), "{:?}", diff);
+    assert!(diff.contains("\x1b[1m\x1b[4m\x1b[32m→"), "{:?}", diff);
+}
@@ -0,0 +1,186 @@
+//! Each scenario is loaded three ways: with psql's `\copy` from a file, with
+//! server-side `COPY FROM` the same file, and by the harness's own protocol
+//! client sending the file as it is. The last two leave psql out, so when
+//! they agree and `\copy` doesn't, the difference is psql's.
+//!
+//! Server-side COPY reads the file itself, so these tests need the server to
+//! run on this machine and a superuser (or a member of
+//! `pg_read_server_files`) to connect as.
+
+use crate::common::client::{Client, Reply};
+use crate::common::pgcopy::{self, Corruption, PgCopy};
+use crate::common::*;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, Permissions};
+use std::os::unix::fs::PermissionsExt;
+use uuid::Uuid;
+
+/// What a table ended up with after one way of loading it.
+#[derive(Debug, PartialEq)]
+struct Load {
+    reply: Reply,
+    rows: Vec<Vec<Option<String>>>,
+}
+
+impl fmt::Display for Load {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.reply {
+            Ok(tag) => write!(f, "{}", tag)?,
+            Err(message) => write!(f, "ERROR: {}", message)?,
+        }
+        for row in &self.rows {
+            let values: Vec<String> = row
+                .iter()
+                .map(|value| value.as_ref().map_or("NULL".to_string(), |value| format!("{:?}", value)))
+                .collect();
+            write!(f, "\n    ({})", values.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The rows of table `n int, s text`, from its binary COPY.
+fn rows(client: &mut Client, table: Uuid) -> Result<Vec<Vec<Option<String>>>, Box<dyn Error>> {
+    let (data, reply) = client.copy_out(&format!(r#"COPY "{}" TO STDOUT (format binary)"#, table))?;
+    reply?;
+    let copy = pgcopy::parse(&data)?;
+    Ok(copy
+        .tuples
+        .into_iter()
+        .map(|tuple| {
+            let n = tuple[0].as_ref().map(|n| i32::from_be_bytes(n[..4].try_into().unwrap()).to_string());
+            let s = tuple[1].as_ref().map(|s| String::from_utf8_lossy(s).into_owned());
+            vec![n, s]
+        })
+        .collect())
+}
+
+/// Loads `data` with `options` into a table of `n int, s text` each of the
+/// three ways, and checks that they all end up the same.
+fn differential(options: &str, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    let dir = tempfile::TempDir::new()?;
+    // The server opens the file as its own operating system user.
+    fs::set_permissions(dir.path(), Permissions::from_mode(0o755))?;
+    let path = dir.path().join("data");
+    fs::write(&path, data)?;
+    fs::set_permissions(&path, Permissions::from_mode(0o644))?;
+
+    let mut client = Client::connect()?;
+    let tables = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+    for table in tables {
+        client.query(&format!(r#"CREATE TABLE "{}" (n int, s text)"#, table))??;
+    }
+
+    let output = run_cmd(
+        "psql",
+        &["-c", &format!(r#"\copy "{}" from '{}' ({})"#, tables[0], path.display(), options)],
+    )?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let psql = if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(stderr
+            .lines()
+            .find_map(|line| line.split_once("ERROR:  ").map(|(_, message)| message.to_string()))
+            .unwrap_or_else(|| stderr.trim().to_string()))
+    };
+    let server = client.query(&format!(r#"COPY "{}" FROM '{}' ({})"#, tables[1], path.display(), options))?;
+    let protocol = client.copy_in(&format!(r#"COPY "{}" FROM STDIN ({})"#, tables[2], options), data)?;
+
+    let mut loads = Vec::new();
+    for (table, reply) in tables.into_iter().zip([psql, server, protocol]) {
+        loads.push(Load {
+            reply,
+            rows: rows(&mut client, table)?,
+        });
+        client.query(&format!(r#"DROP TABLE "{}""#, table))??;
+    }
+
+    let [psql, server, protocol] = &loads[..] else { unreachable!() };
+    if psql == server && server == protocol {
+        return Ok(());
+    }
+    let verdict = if server == protocol {
+        "psql-specific behavior: \\copy differs from both server-side COPY and the protocol client"
+    } else {
+        "server-side COPY and the protocol client differ, so the server reads a file and COPY data differently"
+    };
+    let report = format!(
+        "{}\n  \\copy:            {}\n  server-side COPY: {}\n  protocol client:  {}",
+        verdict, psql, server, protocol
+    );
+    println!("\nLoads of \"{}\" with ({}) differ:\n{}", data.escape_ascii(), options, report);
+    report::record_diff(report);
+    panic!("Differential check failed");
+}
+
+#[test]
+fn test_differential_text() -> Result<(), Box<dyn Error>> {
+    differential("format text", b"1\ta\n2\tb\n3\t\\N\n")
+}
+
+#[test]
+fn test_differential_text_escapes() -> Result<(), Box<dyn Error>> {
+    differential("format text", b"1\ta\\tb\\\\c\n2\t\\x41\\102\\u00e9\n3\t\\.x\n")
+}
+
+#[test]
+fn test_differential_text_end_marker() -> Result<(), Box<dyn Error>> {
+    differential("format text", b"1\ta\n\\.\n")
+}
+
+#[test]
+fn test_differential_text_crlf() -> Result<(), Box<dyn Error>> {
+    differential("format text", b"1\ta\r\n2\tb\r\n")
+}
+
+#[test]
+fn test_differential_text_no_final_newline() -> Result<(), Box<dyn Error>> {
+    differential("format text", b"1\ta\n2\tb")
+}
+
+#[test]
+fn test_differential_text_extra_column() -> Result<(), Box<dyn Error>> {
+    differential("format text", b"1\ta\tb\n")
+}
+
+#[test]
+fn test_differential_text_invalid_utf8() -> Result<(), Box<dyn Error>> {
+    differential("format text", b"1\ta\xff\n")
+}
+
+#[test]
+fn test_differential_csv() -> Result<(), Box<dyn Error>> {
+    differential("format csv", b"1,a\n2,\"b,\"\"c\"\"\"\n3,\n4,\"\"\n")
+}
+
+#[test]
+fn test_differential_csv_header() -> Result<(), Box<dyn Error>> {
+    differential("format csv, header", b"n,s\n1,a\n")
+}
+
+#[test]
+fn test_differential_csv_quoted_line_breaks() -> Result<(), Box<dyn Error>> {
+    differential("format csv", b"1,\"a\nb\"\n2,\"c\r\nd\"\r\n")
+}
+
+#[test]
+fn test_differential_csv_quoted_end_marker() -> Result<(), Box<dyn Error>> {
+    differential("format csv", b"1,\"a\n\\.\nb\"\n2,c\n")
+}
+
+#[test]
+fn test_differential_binary() -> Result<(), Box<dyn Error>> {
+    let copy = PgCopy::new(vec![
+        vec![Some(1i32.to_be_bytes().to_vec()), Some(b"a\n\\.\n".to_vec())],
+        vec![Some(2i32.to_be_bytes().to_vec()), None],
+    ]);
+    differential("format binary", &copy.to_bytes())
+}
+
+#[test]
+fn test_differential_binary_truncated() -> Result<(), Box<dyn Error>> {
+    let copy = PgCopy::new(vec![vec![Some(1i32.to_be_bytes().to_vec()), Some(b"a".to_vec())]]);
+    differential("format binary", &copy.corrupted(Corruption::TruncatedTuple))
+}
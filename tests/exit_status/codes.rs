@@ -0,0 +1,47 @@
+use crate::common::*;
+use std::error::Error;
+use uuid::Uuid;
+
+#[test]
+fn test_psql_exit_status_missing_script() -> Result<(), Box<dyn Error>> {
+    let missing = test_dir().join(format!("{}.sql", Uuid::new_v4()));
+    let output = run_cmd("psql", &["-f", &missing.to_string_lossy()])?;
+    expect_exit_status!(output, 1);
+    isempty!(output.stdout);
+    Ok(())
+}
+
+#[test]
+fn test_psql_exit_status_bad_connection() -> Result<(), Box<dyn Error>> {
+    let env = get_test_environment();
+    let output = run_cmd("psql", &["-h", &env.temp_dir.to_string_lossy(), "-c", "SELECT 1;"])?;
+    expect_exit_status!(output, 2);
+    isempty!(output.stdout);
+    Ok(())
+}
+
+#[test]
+fn test_psql_exit_status_command_error() -> Result<(), Box<dyn Error>> {
+    let output = run_cmd("psql", &["-c", "SELECT 1/0;"])?;
+    expect_exit_status!(output, 1);
+    verify!(output.stderr, "\nERROR:  division by zero\n");
+    Ok(())
+}
+
+#[test]
+fn test_psql_copy_exit_status_missing_file() -> Result<(), Box<dyn Error>> {
+    let test_table = Uuid::new_v4();
+    let missing = test_dir().join(format!("{}.text", test_table));
+    let output = run_cmd("psql", &["-c", &format!(r#"CREATE TABLE "{}" (c1 int8, c2 int8);"#, test_table)])?;
+    expect_create_table!(output);
+    // A failing \copy in -c mode is an ordinary command failure ...
+    let output = run_cmd("psql", &["-c", &format!(r#"\copy "{}" from '{}'"#, test_table, missing.display())])?;
+    expect_exit_status!(output, 1);
+    // ... but a script error under ON_ERROR_STOP when the script comes from stdin.
+    let script = format!(r#"\copy "{}" from '{}'"#, test_table, missing.display());
+    let output = run_cmd_with_input("psql", &["-v", "ON_ERROR_STOP=1"], script.as_bytes())?;
+    expect_exit_status!(output, 3);
+    let output = run_cmd("psql", &["-c", &format!(r#"DROP TABLE "{}";"#, test_table)])?;
+    expect_drop_table!(output);
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use crate::common::*;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Write;
+use std::panic;
+use uuid::Uuid;
+
+/// Where the failing statement sits relative to the `\copy` in the script.
+#[derive(Clone, Copy, Debug)]
+enum Failure {
+    Before,
+    Inside,
+    After,
+}
+
+#[derive(Debug)]
+struct Cell {
+    on_error_stop: bool,
+    single_transaction: bool,
+    on_error_rollback: bool,
+    failure: Failure,
+}
+
+impl Cell {
+    /// The exit status and surviving `c1` values psql's documentation promises
+    /// for this cell. The script inserts 1, copies 3 and 5, then inserts 7.
+    fn expected(&self) -> (i32, &'static str) {
+        // "psql returns ... 3 if an error occurred in a script and the
+        // variable ON_ERROR_STOP was set", otherwise the script runs to the end.
+        let exit_code = if self.on_error_stop { 3 } else { 0 };
+        // With --single-transaction any error aborts the whole transaction,
+        // unless ON_ERROR_ROLLBACK wraps each statement in a savepoint and
+        // psql keeps going.
+        if self.single_transaction && (self.on_error_stop || !self.on_error_rollback) {
+            return (exit_code, "");
+        }
+        let rows = match (self.on_error_stop, self.failure) {
+            (true, Failure::Before) | (true, Failure::Inside) => "1",
+            (true, Failure::After) => "1,3,5",
+            // A bad row fails the whole COPY, but not its neighbours.
+            (false, Failure::Inside) => "1,7",
+            (false, _) => "1,3,5,7",
+        };
+        (exit_code, rows)
+    }
+
+    fn args(&self) -> Vec<&'static str> {
+        let mut args = Vec::new();
+        if self.on_error_stop {
+            args.extend(["-v", "ON_ERROR_STOP=1"]);
+        }
+        if self.single_transaction {
+            args.push("-1");
+        }
+        if self.on_error_rollback {
+            args.extend(["-v", "ON_ERROR_ROLLBACK=on"]);
+        }
+        args
+    }
+}
+
+fn run_cell(cell: &Cell) -> Result<(), Box<dyn Error>> {
+    let test_table = Uuid::new_v4();
+    let output = run_cmd("psql", &["-c", &format!(r#"CREATE TABLE "{}" (c1 int8, c2 int8);"#, test_table)])?;
+    expect_create_table!(output);
+
+    let test_file_path = test_dir().join(format!("{}", test_table));
+    let mut test_file = File::create(&test_file_path)?;
+    writeln!(test_file, r#"INSERT INTO "{}" VALUES (1, 2);"#, test_table)?;
+    if let Failure::Before = cell.failure {
+        writeln!(test_file, "SELECT 1/0;")?;
+    }
+    writeln!(test_file, r#"\copy "{}" from stdin"#, test_table)?;
+    writeln!(test_file, "3\t4")?;
+    if let Failure::Inside = cell.failure {
+        writeln!(test_file, "x\ty")?;
+    }
+    writeln!(test_file, "5\t6")?;
+    writeln!(test_file, r"\.")?;
+    if let Failure::After = cell.failure {
+        writeln!(test_file, "SELECT 1/0;")?;
+    }
+    writeln!(test_file, r#"INSERT INTO "{}" VALUES (7, 8);"#, test_table)?;
+    drop(test_file);
+
+    let test_file_arg = test_file_path.to_string_lossy();
+    let mut args = cell.args();
+    args.extend(["-f", &test_file_arg]);
+    let output = run_cmd("psql", &args)?;
+    let (exit_code, rows) = cell.expected();
+    expect_exit_status!(output, exit_code);
+    fs::remove_file(&test_file_path)?;
+
+    let output = run_cmd("psql", &["-XAtc", &format!(r#"SELECT string_agg(c1::text, ',' ORDER BY c1) FROM "{}";"#, test_table)])?;
+    expect_exit_status!(output, 0);
+    let expected_rows = format!("\n{}\n", rows);
+    verify!(output.stdout, expected_rows.as_str());
+    let output = run_cmd("psql", &["-c", &format!(r#"DROP TABLE "{}";"#, test_table)])?;
+    expect_drop_table!(output);
+    Ok(())
+}
+
+#[test]
+fn test_psql_copy_exit_status_matrix() -> Result<(), Box<dyn Error>> {
+    for on_error_stop in [false, true] {
+        for single_transaction in [false, true] {
+            for on_error_rollback in [false, true] {
+                for failure in [Failure::Before, Failure::Inside, Failure::After] {
+                    let cell = Cell {
+                        on_error_stop,
+                        single_transaction,
+                        on_error_rollback,
+                        failure,
+                    };
+                    match panic::catch_unwind(|| run_cell(&cell)) {
+                        Ok(result) => result.map_err(|e| format!("{:?}: {}", cell, e))?,
+                        Err(_) => panic!("Cell failed: {:?}", cell),
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
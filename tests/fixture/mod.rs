@@ -0,0 +1,105 @@
+use crate::common::fixture;
+use crate::common::pgcopy::{Corruption, PgCopy};
+use crate::common::*;
+use std::error::Error;
+use std::fs;
+use uuid::Uuid;
+
+/// Fields the text and CSV formats have to escape or quote.
+const ROWS: &[&[Option<&str>]] = &[
+    &[Some("tab\there"), Some("back\\slash")],
+    &[Some("line\nbreak"), Some("carriage\rreturn")],
+    &[None, Some("")],
+    &[Some("comma, \"quote\""), Some("\\.")],
+    &[Some("\\N"), Some("\u{8}\u{b}\u{c}")],
+];
+
+#[test]
+fn test_fixture_matches_server() -> Result<(), Box<dyn Error>> {
+    let env = get_test_environment();
+    let query = "VALUES (1::int8, 2::int8), (3, 4)";
+    let output = run_cmd("psql", &["-c", &format!(r#"\copy ({}) to stdout (format text)"#, query)])?;
+    verify!(output.stdout, &fs::read_to_string(&env.file_path_text)?);
+    let output = run_cmd("psql", &["-c", &format!(r#"\copy ({}) to stdout (format csv)"#, query)])?;
+    verify!(output.stdout, &fs::read_to_string(&env.file_path_csv)?);
+    let output = run_cmd("psql", &["-c", &format!(r#"\copy ({}) to stdout (format binary)"#, query)])?;
+    verify_pgcopy!(output.stdout, fs::read(&env.file_path_binary)?);
+    Ok(())
+}
+
+/// Copies `data` into a table of two text columns and checks that the
+/// server writes back exactly the same.
+fn round_trip(format: &str, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    let test_table = Uuid::new_v4();
+    let path = test_dir().join(format!("{}.{}", test_table, format));
+    fs::write(&path, data)?;
+    let output = run_cmd("psql", &["-c", &format!(r#"CREATE TABLE "{}" (a text, b text);"#, test_table)])?;
+    expect_create_table!(output);
+    let output = run_cmd("psql", &["-c", &format!(r#"\copy "{}" from '{}' (format {})"#, test_table, path.display(), format)])?;
+    expect_exit_status!(output, 0);
+    verify!(output.stdout, &format!("COPY {}\n", ROWS.len()));
+    let output = run_cmd("psql", &["-c", &format!(r#"\copy "{}" to stdout (format {})"#, test_table, format)])?;
+    expect_exit_status!(output, 0);
+    if format == "binary" {
+        verify_pgcopy!(output.stdout, data);
+    } else {
+        verify!(output.stdout, std::str::from_utf8(data)?);
+    }
+    let output = run_cmd("psql", &["-c", &format!(r#"DROP TABLE "{}";"#, test_table)])?;
+    expect_drop_table!(output);
+    Ok(())
+}
+
+#[test]
+fn test_fixture_text_escaping() -> Result<(), Box<dyn Error>> {
+    round_trip("text", fixture::text(ROWS).as_bytes())
+}
+
+#[test]
+fn test_fixture_csv_quoting() -> Result<(), Box<dyn Error>> {
+    round_trip("csv", fixture::csv(ROWS).as_bytes())
+}
+
+#[test]
+fn test_fixture_binary() -> Result<(), Box<dyn Error>> {
+    round_trip("binary", &PgCopy::text(ROWS).to_bytes())
+}
+
+#[test]
+fn test_fixture_binary_corrupt() -> Result<(), Box<dyn Error>> {
+    let copy = PgCopy::int8(&[&[Some(1), Some(2)], &[Some(3), Some(4)]]);
+    for (corruption, error) in [
+        (Corruption::Signature, Some("ERROR:  COPY file signature not recognized")),
+        (Corruption::CriticalFlag, Some("ERROR:  unrecognized critical flags in COPY file header")),
+        (Corruption::TruncatedTuple, Some("ERROR:  unexpected EOF in COPY data")),
+        (Corruption::FieldCount(3), Some("ERROR:  row field count is 3, expected 2")),
+        (Corruption::FieldCount(1), Some("ERROR:  row field count is 1, expected 2")),
+        (Corruption::HugeLength, Some("ERROR:  out of memory")),
+        // The server takes the end of the data as the end of the file.
+        (Corruption::MissingTrailer, None),
+        (Corruption::TrailingData, Some("ERROR:  received copy data after EOF marker")),
+    ] {
+        let test_table = Uuid::new_v4();
+        let path = test_dir().join(format!("{}.binary", test_table));
+        fs::write(&path, copy.corrupted(corruption))?;
+        let output = run_cmd("psql", &["-c", &format!(r#"CREATE TABLE "{}" (c1 int8, c2 int8);"#, test_table)])?;
+        expect_create_table!(output);
+        let output = run_cmd("psql", &["-c", &format!(r#"\copy "{}" from '{}' (format binary)"#, test_table, path.display())])?;
+        let rows = match error {
+            Some(error) => {
+                expect_exit_status!(output, 1);
+                verify_contains!(output.stderr, error);
+                "0"
+            }
+            None => {
+                expect_copy_two!(output);
+                "2"
+            }
+        };
+        let output = run_cmd("psql", &["-Atc", &format!(r#"SELECT count(*) FROM "{}";"#, test_table)])?;
+        verify!(output.stdout, &format!("{}\n", rows));
+        let output = run_cmd("psql", &["-c", &format!(r#"DROP TABLE "{}";"#, test_table)])?;
+        expect_drop_table!(output);
+    }
+    Ok(())
+}
@@ -0,0 +1,356 @@
+//! Fuzzing of `\copy from stdin`: byte streams mutated from a corpus are fed
+//! to psql through a script and through a pipe, against the mock backend, in
+//! each format. A run fails on a crash, a hang, or desync, where psql sends a
+//! query or disconnects while the backend is still in COPY.
+//!
+//! psql isn't instrumented for coverage, so its behavior guides the fuzzer
+//! instead: an input that makes psql behave in a way no corpus entry has, by
+//! exit status, how the COPY ended, how many CopyData messages it took and
+//! what psql printed, joins the corpus. Each finding is shrunk to a minimal
+//! reproducer.
+
+use crate::common::mock::MockBackend;
+use crate::common::wire;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::mem;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// The checked-in seeds, read on every run.
+const SEEDS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fuzz/corpus");
+
+const METHODS: [&str; 2] = ["script", "pipe"];
+const FORMATS: [&str; 3] = ["text", "csv", "binary"];
+const MAX_LEN: usize = 4096;
+
+/// The seed unless `PSQL_FUZZ_SEED`, `PSQL_FUZZ_ITERATIONS` or
+/// `PSQL_FUZZ_CORPUS` is set.
+const DEFAULT_SEED: u64 = 0x5053_514c_434f_5059;
+
+/// What a script has after the data: the end of the COPY, unless psql has
+/// already seen one, and a query that must reach a backend no longer in COPY.
+const TRAILER: &[u8] = b"\n\\.\nSELECT 'in sync';\n";
+
+/// Byte strings COPY input handling treats specially, inserted whole.
+const TOKENS: &[&[u8]] = &[
+    b"\\.",
+    b"\\.\n",
+    b"\n",
+    b"\r\n",
+    b"\r",
+    b"\\",
+    b"\t",
+    b",",
+    b"\"",
+    b"\\N",
+    b"\0",
+    b"\x03",
+    b"\x04",
+    b"\xff",
+    b"\xff\xff",
+    b"PGCOPY\n\xff\r\n\0",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Finding {
+    /// psql was killed by this signal.
+    Crash(i32),
+    /// psql was still running after the suite timeout.
+    Hang,
+    /// The mock backend's protocol errors.
+    Desync(Vec<String>),
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::Crash(signal) => write!(f, "crash: psql killed by signal {}", signal),
+            Finding::Hang => write!(f, "hang: psql still running after {:?}", suite_timeout!()),
+            Finding::Desync(errors) => write!(f, "desync: {}", errors.join("; ")),
+        }
+    }
+}
+
+struct Run {
+    /// What psql did, as the fuzzer tells behaviors apart.
+    behavior: String,
+    finding: Option<Finding>,
+}
+
+/// Runs psql on `input` as COPY data in `format`, read from the script
+/// itself or piped to `-c`.
+fn run(method: &str, format: &str, input: &[u8]) -> io::Result<Run> {
+    let backend = MockBackend::start()?;
+    let dir = tempfile::TempDir::new()?;
+    let copy = format!(r"\copy t from stdin (format {})", format);
+    let mut args = backend.psql_args();
+    let mut stdin = Vec::new();
+    if method == "script" {
+        let script = dir.path().join("script.sql");
+        fs::write(&script, [copy.as_bytes(), b"\n", input, TRAILER].concat())?;
+        args.extend(["-f".to_string(), script.to_string_lossy().into_owned()]);
+    } else {
+        args.extend(["-c".to_string(), copy]);
+        stdin = input.to_vec();
+    }
+
+    // Whatever the script goes on to write lands in the temporary directory,
+    // which `mutate` makes sure of; `~` means it too.
+    let stderr_path = dir.path().join("stderr");
+    let mut child = Command::new("psql")
+        .args(&args)
+        .current_dir(dir.path())
+        .env("HOME", dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(File::create(&stderr_path)?)
+        .spawn()?;
+    let mut pipe = child.stdin.take().unwrap();
+    // psql may exit before reading all of its input, so a broken pipe is not an error here.
+    let writer = thread::spawn(move || {
+        let _ = pipe.write_all(&stdin);
+    });
+    let deadline = Instant::now() + suite_timeout!();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        thread::sleep(Duration::from_millis(5));
+    };
+    let _ = writer.join();
+    backend.wait_for_disconnect(suite_timeout!());
+
+    let stderr = fs::read(&stderr_path)?;
+    let stderr = String::from_utf8_lossy(&stderr).replace(&*dir.path().to_string_lossy(), "");
+    // Line numbers and byte counts would make every input look new.
+    let message: String = stderr.lines().next().unwrap_or_default().chars().filter(|c| !c.is_ascii_digit()).collect();
+    let messages = backend.messages();
+    let copy_data = messages.iter().filter(|m| m.tag == Some(wire::COPY_DATA)).count();
+    let queries = messages.iter().filter(|m| m.tag == Some(wire::QUERY)).count();
+    let end = match backend.copy_end().and_then(|m| m.tag) {
+        Some(wire::COPY_DONE) => "CopyDone",
+        Some(_) => "CopyFail",
+        None => "no end",
+    };
+    let behavior = format!(
+        "{} {} {:?} {} CopyData ~2^{} {} queries {}",
+        method,
+        format,
+        status.and_then(|status| status.code()),
+        end,
+        usize::BITS - copy_data.leading_zeros(),
+        queries,
+        message
+    );
+
+    let protocol_errors = backend.protocol_errors();
+    let finding = match status {
+        None => Some(Finding::Hang),
+        Some(status) if status.signal().is_some() => Some(Finding::Crash(status.signal().unwrap())),
+        _ if !protocol_errors.is_empty() => Some(Finding::Desync(protocol_errors)),
+        _ => None,
+    };
+    Ok(Run { behavior, finding })
+}
+
+/// xorshift64*, enough to pick mutations and repeatable from a seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// `input` with one to four random bit flips, byte changes, token
+/// insertions, deletions, duplications or splices from another entry.
+fn mutate(rng: &mut Rng, input: &[u8], corpus: &[Vec<u8>]) -> Vec<u8> {
+    let mut out = input.to_vec();
+    for _ in 0..1 + rng.below(4) {
+        let at = rng.below(out.len() + 1);
+        let start = at.min(out.len().saturating_sub(1));
+        let end = (start + 1 + rng.below(8)).min(out.len());
+        match rng.below(6) {
+            0 if !out.is_empty() => out[start] ^= 1 << rng.below(8),
+            1 if !out.is_empty() => out[start] = rng.below(256) as u8,
+            2 => {
+                let token = TOKENS[rng.below(TOKENS.len())];
+                out.splice(at..at, token.iter().copied());
+            }
+            3 if !out.is_empty() => {
+                out.drain(start..end);
+            }
+            4 if !out.is_empty() => {
+                let copy = out[start..end].to_vec();
+                out.splice(end..end, copy);
+            }
+            _ => {
+                let other = &corpus[rng.below(corpus.len())];
+                let from = rng.below(other.len() + 1);
+                out.splice(at..at, other[from..].iter().copied());
+            }
+        }
+    }
+    // Data after the end of the COPY runs as script. Without `!`, `|`,
+    // backticks and `program` nothing starts a shell (`\!`, `\o |cmd`,
+    // `` \echo `cmd` ``, `\copy ... program`), and without `/` and `\cd`
+    // every file name psql writes to is in its working directory.
+    out.retain(|&b| !matches!(b, b'!' | b'|' | b'`' | b'/'));
+    for word in [&b"\\cd"[..], b"program"] {
+        while let Some(at) = out.windows(word.len()).position(|window| window.eq_ignore_ascii_case(word)) {
+            out.drain(at..at + word.len());
+        }
+    }
+    out.truncate(MAX_LEN);
+    out
+}
+
+/// Removes ever smaller chunks of `input` for as long as it still finds the
+/// same kind of problem.
+fn minimize(method: &str, format: &str, input: &[u8], finding: &Finding) -> io::Result<Vec<u8>> {
+    let mut input = input.to_vec();
+    let mut chunk = input.len() / 2;
+    while chunk > 0 {
+        let mut at = 0;
+        while at < input.len() {
+            let candidate = [&input[..at], &input[(at + chunk).min(input.len())..]].concat();
+            let found = run(method, format, &candidate)?.finding;
+            if found.is_some_and(|found| mem::discriminant(&found) == mem::discriminant(finding)) {
+                input = candidate;
+            } else {
+                at += chunk;
+            }
+        }
+        chunk /= 2;
+    }
+    Ok(input)
+}
+
+fn read_corpus(dir: &Path) -> io::Result<Vec<Vec<u8>>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    paths.retain(|path| path.is_file());
+    paths.sort();
+    paths.iter().map(fs::read).collect()
+}
+
+fn hash(input: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    input.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+fn env_number(name: &str) -> Option<u64> {
+    let value = env::var(name).ok()?;
+    Some(value.parse().unwrap_or_else(|_| panic!("{} must be a number, not {:?}", name, value)))
+}
+
+#[test]
+fn test_fuzz_copy_from_stdin() -> Result<(), Box<dyn Error>> {
+    let explicit_iterations = env_number("PSQL_FUZZ_ITERATIONS");
+    let iterations = explicit_iterations.unwrap_or(32);
+    let saved = env::var_os("PSQL_FUZZ_CORPUS").map(PathBuf::from);
+    // A plain `cargo test` always tries the same inputs; only a run asked to
+    // fuzz for real explores new ones.
+    let seed = env_number("PSQL_FUZZ_SEED").unwrap_or_else(|| {
+        if explicit_iterations.is_some() || saved.is_some() {
+            SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_nanos() as u64
+        } else {
+            DEFAULT_SEED
+        }
+    });
+    println!("Fuzzing with PSQL_FUZZ_SEED={}", seed);
+    let mut rng = Rng(seed.max(1));
+
+    let mut corpus = read_corpus(Path::new(SEEDS))?;
+    if let Some(dir) = &saved {
+        fs::create_dir_all(dir.join("findings"))?;
+        corpus.extend(read_corpus(dir)?);
+    }
+
+    let mut behaviors = HashSet::new();
+    let mut findings = Vec::new();
+    let mut found = HashSet::new();
+    let mut check = |input: &[u8], behaviors: &mut HashSet<String>| -> io::Result<bool> {
+        let mut new = false;
+        for method in METHODS {
+            for format in FORMATS {
+                let run = run(method, format, input)?;
+                new |= behaviors.insert(run.behavior);
+                let Some(finding) = run.finding else { continue };
+                // One reproducer per kind of finding and case is enough.
+                if !found.insert((method, format, mem::discriminant(&finding))) {
+                    continue;
+                }
+                let reproducer = minimize(method, format, input, &finding)?;
+                let mut report = format!(
+                    "{} through {} in {}\n  input: \"{}\"",
+                    finding,
+                    method,
+                    format,
+                    reproducer.escape_ascii()
+                );
+                if let Some(dir) = &saved {
+                    let path = dir.join("findings").join(format!("{}-{}-{}", method, format, hash(&reproducer)));
+                    fs::write(&path, &reproducer)?;
+                    report.push_str(&format!("\n  saved as {}", path.display()));
+                }
+                findings.push(report);
+            }
+        }
+        Ok(new)
+    };
+
+    for input in &corpus {
+        check(input, &mut behaviors)?;
+    }
+    let seeds = corpus.len();
+    for _ in 0..iterations {
+        let parent = &corpus[rng.below(corpus.len())];
+        let input = mutate(&mut rng, parent, &corpus);
+        if check(&input, &mut behaviors)? {
+            if let Some(dir) = &saved {
+                fs::write(dir.join(hash(&input)), &input)?;
+            }
+            corpus.push(input);
+        }
+    }
+    println!(
+        "{} iterations, {} new corpus entries, {} behaviors",
+        iterations,
+        corpus.len() - seeds,
+        behaviors.len()
+    );
+
+    if !findings.is_empty() {
+        for finding in &findings {
+            println!("{}", finding);
+        }
+        crate::common::report::record_diff(findings.join("\n"));
+        panic!("Fuzzing found {} problems; rerun with PSQL_FUZZ_SEED={}", findings.len(), seed);
+    }
+    Ok(())
+}
@@ -0,0 +1 @@
+mod terminal_stdin;
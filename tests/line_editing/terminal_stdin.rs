@@ -0,0 +1,120 @@
+use crate::common::mock::MockBackend;
+use crate::common::*;
+use expectrl::Eof;
+use std::error::Error;
+
+fn spawn(profile: Profile, backend: &MockBackend, log_file: &std::fs::File) -> Result<PtySession, expectrl::Error> {
+    let mut command = profile.command();
+    command.args(backend.psql_args()).args(["-P", "pager=off"]);
+    let mut session = spawn_session(command, log_file)?;
+    set_timeout(&mut session, suite_timeout!());
+    Ok(session)
+}
+
+/// The profiles to test expectations of, leaving out those nobody has
+/// checked yet.
+fn profiles() -> Vec<Profile> {
+    let (verified, unverified): (Vec<Profile>, Vec<Profile>) = Profile::all().into_iter().partition(Profile::verified);
+    for profile in unverified {
+        println!("Skipping profile {}: its expectations haven't been checked against such a psql", profile.name());
+    }
+    for profile in &verified {
+        println!("Profile: {}", profile.name());
+    }
+    verified
+}
+
+#[test]
+fn test_psql_copy_tab() -> Result<(), Box<dyn Error>> {
+    // Whatever reads the line, nothing here differs between profiles.
+    for profile in Profile::all() {
+        println!("Profile: {}", profile.name());
+        let backend = MockBackend::start()?;
+
+        let temp_file = tempfile::NamedTempFile::new()?;
+        let mut session = spawn(profile, &backend, temp_file.as_file())?;
+
+        expect_prompt!(&mut session, PROMPT1, &temp_file);
+        session.send_line(r#"\copy t from stdin"#)?;
+        expect_prompt!(&mut session, PROMPT3, &temp_file);
+        session.send_line("1\t2")?;
+        expect_prompt!(&mut session, PROMPT3, &temp_file);
+        session.send_line("\\.")?;
+        expect_prompt!(&mut session, PROMPT1, &temp_file);
+        session.send_line("\\q")?;
+        session.expect(Eof)?;
+
+        // A tab in COPY data is data, whatever reads the line.
+        assert_eq!(backend.copy_data(), vec![b"1\t2\n\\.\n".to_vec()]);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_psql_tab_completion() -> Result<(), Box<dyn Error>> {
+    for profile in profiles() {
+        let backend = MockBackend::start()?;
+
+        let temp_file = tempfile::NamedTempFile::new()?;
+        let mut session = spawn(profile, &backend, temp_file.as_file())?;
+
+        expect_prompt!(&mut session, PROMPT1, &temp_file);
+        session.send("sel\t1;\r")?;
+        expect_prompt!(&mut session, PROMPT1, &temp_file);
+        session.send_line("\\q")?;
+        session.expect(Eof)?;
+
+        let queries: Vec<String> = backend
+            .messages()
+            .iter()
+            .filter(|m| m.tag == Some(wire::QUERY))
+            .map(|m| wire::cstring(&m.body))
+            .collect();
+        let expected = if profile.completes() { "select 1;" } else { "sel\t1;" };
+        assert_eq!(queries, vec![expected]);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_psql_copy_history() -> Result<(), Box<dyn Error>> {
+    for profile in profiles() {
+        let backend = MockBackend::start()?;
+
+        let temp_file = tempfile::NamedTempFile::new()?;
+        let mut session = spawn(profile, &backend, temp_file.as_file())?;
+
+        expect_prompt!(&mut session, PROMPT1, &temp_file);
+        session.send_line(r#"\copy t from stdin"#)?;
+        expect_prompt!(&mut session, PROMPT3, &temp_file);
+        session.send_line("1\t2")?;
+        expect_prompt!(&mut session, PROMPT3, &temp_file);
+        session.send_line("\\.")?;
+        expect_prompt!(&mut session, PROMPT1, &temp_file);
+        session.send_line("\\s")?;
+        expect_prompt!(&mut session, PROMPT1, &temp_file);
+
+        // COPY data lines never go into the history, only the commands.
+        let screen = session.get_stream().screen();
+        let top = screen.find_row(r"psql_tester1> \s").unwrap();
+        if profile.keeps_history() {
+            verify!(screen.text_from(top).into_bytes(), r#"
+psql_tester1> \s
+\copy t from stdin
+\s
+
+psql_tester1>
+"#);
+        } else {
+            verify!(screen.text_from(top).into_bytes(), r#"
+psql_tester1> \s
+
+psql_tester1>
+"#);
+        }
+
+        session.send_line("\\q")?;
+        session.expect(Eof)?;
+    }
+    Ok(())
+}
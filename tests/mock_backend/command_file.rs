@@ -0,0 +1,60 @@
+use crate::common::mock::{MockBackend, Response};
+use crate::common::*;
+use std::error::Error;
+
+#[test]
+fn test_psql_copy_to() -> Result<(), Box<dyn Error>> {
+    let backend = MockBackend::start()?;
+    backend.set_copy_out(b"1,2\n3,4\n");
+    let out_file = tempfile::NamedTempFile::new()?;
+    let mut args = backend.psql_args();
+    args.extend(["-c".to_string(), format!(r#"\copy t to '{}' (format csv)"#, out_file.path().display())]);
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let output = run_cmd("psql", &args)?;
+    expect_copy_two!(output);
+    verify!(std::fs::read(out_file.path())?, "\n1,2\n3,4\n");
+    Ok(())
+}
+
+#[test]
+fn test_psql_query() -> Result<(), Box<dyn Error>> {
+    let backend = MockBackend::start()?;
+    backend.respond(
+        "SELECT",
+        Response::Rows {
+            columns: vec!["c1".to_string(), "c2".to_string()],
+            rows: vec![
+                vec![Some("1".to_string()), Some("2".to_string())],
+                vec![Some("3".to_string()), None],
+            ],
+        },
+    );
+    let mut args = backend.psql_args();
+    args.extend(["-c".to_string(), "SELECT * FROM t;".to_string()]);
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let output = run_cmd("psql", &args)?;
+    expect_exit_status!(output, 0);
+    verify!(output.stdout, r#"
+ c1 | c2 
+----+----
+ 1  | 2
+ 3  | 
+(2 rows)
+
+"#);
+    isempty!(output.stderr);
+    Ok(())
+}
+
+#[test]
+fn test_psql_query_error() -> Result<(), Box<dyn Error>> {
+    let backend = MockBackend::start()?;
+    backend.respond("SELECT", Response::Error("no such table".to_string()));
+    let mut args = backend.psql_args();
+    args.extend(["-c".to_string(), "SELECT * FROM t;".to_string()]);
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let output = run_cmd("psql", &args)?;
+    expect_exit_status!(output, 1);
+    verify!(output.stderr, "\nERROR:  no such table\n");
+    Ok(())
+}
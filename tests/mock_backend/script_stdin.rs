@@ -0,0 +1,58 @@
+use crate::common::mock::MockBackend;
+use crate::common::wire;
+use crate::common::*;
+use std::error::Error;
+use std::io::Write;
+
+fn run_script(backend: &MockBackend, script: &[u8]) -> Result<Output, Box<dyn Error>> {
+    let mut test_file = tempfile::NamedTempFile::new()?;
+    test_file.write_all(script)?;
+    let test_file_path = test_file.path().to_string_lossy().into_owned();
+    let mut args = backend.psql_args();
+    args.extend(["-f".to_string(), test_file_path]);
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    Ok(run_cmd("psql", &args)?)
+}
+
+#[test]
+fn test_psql_copy_text() -> Result<(), Box<dyn Error>> {
+    let backend = MockBackend::start()?;
+    let output = run_script(&backend, b"\\copy t from stdin\n1\t2\n3\t4\n\\.\n")?;
+    expect_copy_two!(output);
+    // psql batches whole lines and forwards the end-of-data marker too.
+    assert_eq!(backend.copy_data(), vec![b"1\t2\n3\t4\n\\.\n".to_vec()]);
+    assert_eq!(backend.copy_end().and_then(|m| m.tag), Some(wire::COPY_DONE));
+    Ok(())
+}
+
+#[test]
+fn test_psql_copy_csv() -> Result<(), Box<dyn Error>> {
+    let backend = MockBackend::start()?;
+    let output = run_script(&backend, b"\\copy t from stdin (format csv)\n1,2\n3,4\n\\.\n")?;
+    expect_copy_two!(output);
+    assert_eq!(backend.copy_data(), vec![b"1,2\n3,4\n\\.\n".to_vec()]);
+    assert_eq!(backend.copy_end().and_then(|m| m.tag), Some(wire::COPY_DONE));
+    Ok(())
+}
+
+#[test]
+fn test_psql_copy_eof() -> Result<(), Box<dyn Error>> {
+    let backend = MockBackend::start()?;
+    let output = run_script(&backend, b"\\copy t from stdin\n1\t2\n3\t4\n")?;
+    expect_copy_two!(output);
+    // Running out of script ends the COPY normally, not with CopyFail.
+    assert_eq!(backend.copy_data(), vec![b"1\t2\n3\t4\n".to_vec()]);
+    assert_eq!(backend.copy_end().and_then(|m| m.tag), Some(wire::COPY_DONE));
+    Ok(())
+}
+
+#[test]
+fn test_psql_copy_binary_in_names() -> Result<(), Box<dyn Error>> {
+    let backend = MockBackend::start()?;
+    // Only the format option picks binary COPY, not a table or column name.
+    let output = run_script(&backend, b"\\copy binary_data (\"binary\") from stdin\n1\n3\n\\.\n")?;
+    expect_copy_two!(output);
+    assert_eq!(backend.copy_data(), vec![b"1\n3\n\\.\n".to_vec()]);
+    assert_eq!(backend.copy_end().and_then(|m| m.tag), Some(wire::COPY_DONE));
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use crate::common::mock::MockBackend;
+use crate::common::{psql_command, set_timeout, spawn_session, PROMPT1, PROMPT3};
+use crate::common::wire;
+use expectrl::Eof;
+use std::error::Error;
+use std::io::Write;
+
+#[test]
+fn test_psql_copy_text() -> Result<(), Box<dyn Error>> {
+    let backend = MockBackend::start()?;
+
+    let temp_file = tempfile::NamedTempFile::new()?;
+    let log_file = temp_file.as_file();
+
+    let mut command = psql_command();
+    command.args(backend.psql_args());
+    let mut session = spawn_session(command, log_file)?;
+
+    set_timeout(&mut session, suite_timeout!());
+
+    expect_prompt!(&mut session, PROMPT1, &temp_file, backend);
+    session.send_line(r#"\copy t from '/dev/tty'"#)?;
+    expect!(&mut session, "Enter data to be copied followed by a newline.", &temp_file, backend);
+    expect_prompt!(&mut session, PROMPT3, &temp_file, backend);
+    session.send_line("1\t2")?;
+    expect_prompt!(&mut session, PROMPT3, &temp_file, backend);
+    session.send_line("3\t4")?;
+    expect_prompt!(&mut session, PROMPT3, &temp_file, backend);
+    write!(session, "\x04")?;
+    expect!(&mut session, "COPY 2", &temp_file, backend);
+    expect_prompt!(&mut session, PROMPT1, &temp_file, backend);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+
+    assert_eq!(backend.copy_data(), vec![b"1\t2\n3\t4\n".to_vec()]);
+    assert_eq!(backend.copy_end().and_then(|m| m.tag), Some(wire::COPY_DONE));
+    Ok(())
+}
@@ -1,6 +1,25 @@
 #[macro_use]
 mod common;
+pub mod auth;
+pub mod cancel;
 pub mod command_file;
+pub mod connect;
+pub mod connection_loss;
+pub mod diff;
+pub mod differential;
+pub mod exit_status;
+pub mod fixture;
+pub mod fuzz;
+pub mod line_editing;
+pub mod mock_backend;
+pub mod pgcopy;
+pub mod prompt;
+pub mod query_buffer;
+pub mod round_trip;
 pub mod script_stdin;
+pub mod standby;
+pub mod terminal_screen;
 pub mod terminal_tty;
 pub mod terminal_stdin;
+pub mod tls;
+pub mod wire_capture;
@@ -0,0 +1,112 @@
+use crate::common::pgcopy::{self, PgCopy};
+use crate::common::*;
+use std::error::Error;
+use std::fs;
+use uuid::Uuid;
+
+fn int8(value: i64) -> Option<Vec<u8>> {
+    Some(value.to_be_bytes().to_vec())
+}
+
+/// The fixture's rows as the server writes them in binary, so the parser is
+/// checked against real output rather than against `PgCopy::to_bytes`.
+fn server_copy() -> Result<Vec<u8>, Box<dyn Error>> {
+    let output = run_cmd("psql", &["-c", r#"\copy (VALUES (1::int8, 2::int8), (3, 4)) to stdout (format binary)"#])?;
+    expect_exit_status!(output, 0);
+    Ok(output.stdout)
+}
+
+#[test]
+fn test_pgcopy_server_output() -> Result<(), Box<dyn Error>> {
+    let copy = pgcopy::parse(&server_copy()?)?;
+    assert_eq!(
+        copy,
+        PgCopy {
+            flags: 0,
+            extension: Vec::new(),
+            tuples: vec![vec![int8(1), int8(2)], vec![int8(3), int8(4)]],
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_pgcopy_copy_to() -> Result<(), Box<dyn Error>> {
+    let env = get_test_environment();
+    let path = test_dir().join(format!("{}.binary", Uuid::new_v4()));
+
+    // The same rows as the fixture, copied back out, give the same file.
+    let output = run_cmd("psql", &["-c", &format!(
+        r#"\copy (VALUES (1::int8, 2::int8), (3, 4)) to '{}' (format binary)"#,
+        path.display()
+    )])?;
+    expect_copy_two!(output);
+    verify_pgcopy!(fs::read(&path)?, fs::read(&env.file_path_binary)?);
+
+    // As int4 every field is half as long and one row has a NULL.
+    let output = run_cmd("psql", &["-c", &format!(
+        r#"\copy (VALUES (1::int4, 2::int4), (3, NULL)) to '{}' (format binary)"#,
+        path.display()
+    )])?;
+    expect_copy_two!(output);
+    let actual = pgcopy::parse(&fs::read(&path)?)?;
+    let expected = pgcopy::parse(&fs::read(&env.file_path_binary)?)?;
+    assert_eq!(
+        pgcopy::differences(&actual, &expected),
+        [
+            "tuple 1 field 1 length 4 vs 8",
+            "tuple 1 field 2 length 4 vs 8",
+            "tuple 2 field 1 length 4 vs 8",
+            "tuple 2 field 2 is NULL vs not NULL",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_pgcopy_invalid() -> Result<(), Box<dyn Error>> {
+    let server = server_copy()?;
+    let error = |bytes: &[u8]| pgcopy::parse(bytes).unwrap_err().to_string();
+
+    assert_eq!(error(b"PGCOPY"), "file is shorter than the signature at byte 0");
+    assert_eq!(
+        error(b"PGCOPY\n\xff\n\0\0\0\0\0\0"),
+        r#"signature is "PGCOPY\n\xff\n\x00\x00", not "PGCOPY\n\xff\r\n\x00" at byte 0"#
+    );
+
+    let mut flags = server.clone();
+    flags[11] = 0x80;
+    assert_eq!(error(&flags), "unknown critical flags 0x80000000 at byte 11");
+    // Like a server since v12, an OID field after each count isn't read.
+    let mut oids = server.clone();
+    oids[12] = 0x01;
+    assert_eq!(error(&oids), "OID flag set, but tuples have no OID fields since PostgreSQL 12 at byte 11");
+
+    // The server's file: signature, flags at 11, extension length at 15, then
+    // tuples at 19 and 45 of a count and two 8-byte fields each, and the
+    // trailer at 71.
+    assert_eq!(server.len(), 73);
+
+    let mut extension = server.clone();
+    extension[18] = 100;
+    assert_eq!(error(&extension), "header extension needs 100 bytes but only 54 are left at byte 19");
+
+    let mut count = server.clone();
+    count[46] = 3;
+    assert_eq!(error(&count), "tuple 2 has 3 fields, tuple 1 has 2 at byte 45");
+
+    let mut length = server.clone();
+    length[21..25].copy_from_slice(&(-2i32).to_be_bytes());
+    assert_eq!(error(&length), "tuple 1 field 1 length is -2 at byte 21");
+
+    let mut long = server.clone();
+    long[59..63].copy_from_slice(&20i32.to_be_bytes());
+    assert_eq!(error(&long), "tuple 2 field 2 needs 20 bytes but only 10 are left at byte 63");
+
+    assert_eq!(error(&server[..72]), "tuple 3 field count needs 2 bytes but only 1 are left at byte 71");
+
+    let mut trailing = server.clone();
+    trailing.push(0);
+    assert_eq!(error(&trailing), "1 bytes follow the trailer at byte 73");
+    Ok(())
+}
@@ -0,0 +1,19 @@
+mod sentinel;
+mod terminal_stdin;
+mod terminal_tty;
+
+use crate::common::mock::MockBackend;
+use crate::common::*;
+use std::error::Error;
+use tempfile::NamedTempFile;
+
+/// Interactive psql on the mock backend with PROMPT3 set to `prompt`.
+fn start(prompt: &str) -> Result<(MockBackend, NamedTempFile, PtySession), Box<dyn Error>> {
+    let backend = MockBackend::start()?;
+    let temp_file = NamedTempFile::new()?;
+    let mut command = psql_command();
+    command.args(backend.psql_args()).arg("-v").arg(format!("PROMPT3={}", prompt));
+    let mut session = spawn_session(command, temp_file.as_file())?;
+    set_timeout(&mut session, suite_timeout!());
+    Ok((backend, temp_file, session))
+}
@@ -0,0 +1,85 @@
+use crate::common::mock::{self, MockBackend};
+use crate::common::*;
+use expectrl::Eof;
+use std::error::Error;
+use std::io::Write;
+
+#[test]
+fn test_psql_prompt_ignores_psqlrc() -> Result<(), Box<dyn Error>> {
+    let backend = MockBackend::start()?;
+    let psqlrc = tempfile::NamedTempFile::new()?;
+    writeln!(psqlrc.as_file(), r#"\set PROMPT1 'custom> '"#)?;
+
+    let temp_file = tempfile::NamedTempFile::new()?;
+    let log_file = temp_file.as_file();
+
+    let mut command = psql_command();
+    command.args(backend.psql_args()).env("PSQLRC", psqlrc.path());
+    let mut session = spawn_session(command, log_file)?;
+
+    set_timeout(&mut session, suite_timeout!());
+
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    // -X skipped the psqlrc; running it by hand does change the prompt.
+    session.send_line(format!(r#"\i {}"#, psqlrc.path().display()))?;
+    expect_prompt!(&mut session, "custom> ", &temp_file);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+    Ok(())
+}
+
+#[test]
+fn test_psql_prompt_non_superuser() -> Result<(), Box<dyn Error>> {
+    let backend = MockBackend::start()?;
+    backend.set_superuser(false);
+
+    let temp_file = tempfile::NamedTempFile::new()?;
+    let log_file = temp_file.as_file();
+
+    let mut command = psql_command();
+    command.args(backend.psql_args());
+    let mut session = spawn_session(command, log_file)?;
+
+    set_timeout(&mut session, suite_timeout!());
+
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    // psql's default prompt would have ended in `=>` here rather than `=#`.
+    session.send_line(r#"\set PROMPT1 '%/%R%# '"#)?;
+    expect_prompt!(&mut session, &format!("{}=> ", mock::DATABASE), &temp_file);
+    session.send_line(r#"\copy t from stdin"#)?;
+    expect_prompt!(&mut session, PROMPT3, &temp_file);
+    session.send_line("\\.")?;
+    expect_screen!(&mut session, "COPY 0", &temp_file);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+    Ok(())
+}
+
+#[test]
+fn test_psql_prompt_continuation() -> Result<(), Box<dyn Error>> {
+    let backend = MockBackend::start()?;
+    // A default prompt would show all 63 characters of the name.
+    let database = "a_database_name_long_enough_to_push_the_default_prompt_past_col";
+
+    let temp_file = tempfile::NamedTempFile::new()?;
+    let log_file = temp_file.as_file();
+
+    let mut command = psql_command();
+    command.args(backend.psql_args()).args(["-d", database]);
+    let mut session = spawn_session(command, log_file)?;
+
+    set_timeout(&mut session, suite_timeout!());
+
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("select")?;
+    expect_prompt!(&mut session, PROMPT2, &temp_file);
+    session.send_line("1;")?;
+    expect_screen!(&mut session, "SELECT", &temp_file);
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+
+    let startup = &backend.messages()[0];
+    assert!(String::from_utf8_lossy(&startup.body).contains(database));
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use super::start;
+use crate::common::screen::wait_for_screen;
+use crate::common::*;
+use expectrl::Eof;
+use std::error::Error;
+use std::time::Duration;
+
+#[test]
+fn test_psql_copy_prompt_escapes() -> Result<(), Box<dyn Error>> {
+    for (format, row) in [("text", "1\t2"), ("csv", "1,2")] {
+        let (_backend, temp_file, mut session) = start("%/:%n:%x%R%#> ")?;
+
+        expect_prompt!(&mut session, PROMPT1, &temp_file);
+        session.send_line(format!(r#"\copy t from '/dev/tty' (format {})"#, format))?;
+        // While COPY runs the connection is busy, which %x shows as `*`, and
+        // %R shows nothing.
+        expect_prompt!(&mut session, "mock:mock:*#> ", &temp_file);
+        session.send_line(row)?;
+        expect_prompt!(&mut session, "mock:mock:*#> ", &temp_file);
+        session.send("\x04")?;
+        expect_screen!(&mut session, "COPY 1", &temp_file);
+        expect_prompt!(&mut session, PROMPT1, &temp_file);
+        session.send_line("\\q")?;
+        session.expect(Eof)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_psql_copy_prompt_line_number() -> Result<(), Box<dyn Error>> {
+    for (format, row) in [("text", "1\t2"), ("csv", "1,2")] {
+        let (_backend, temp_file, mut session) = start("%l> ")?;
+
+        expect_prompt!(&mut session, PROMPT1, &temp_file);
+        session.send_line(format!(r#"\copy t from '/dev/tty' (format {})"#, format))?;
+        // Data lines are read from /dev/tty, not from the stream commands
+        // come from, so they don't count as lines of the statement.
+        expect_prompt!(&mut session, "1> ", &temp_file);
+        session.send_line(row)?;
+        expect_prompt!(&mut session, "1> ", &temp_file);
+        session.send_line(row)?;
+        expect_prompt!(&mut session, "1> ", &temp_file);
+        session.send("\x04")?;
+        expect_screen!(&mut session, "COPY 2", &temp_file);
+        expect_prompt!(&mut session, PROMPT1, &temp_file);
+
+        session.send_line("\\q")?;
+        session.expect(Eof)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_psql_copy_prompt_invisible() -> Result<(), Box<dyn Error>> {
+    let (_backend, temp_file, mut session) = start("%[%033[1m%]copy%[%033[0m%]> ")?;
+
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line(r#"\copy t from '/dev/tty'"#)?;
+    // Neither the escape sequence nor the markers around it take up room on
+    // the screen, and the escape sequence still takes effect.
+    expect_prompt!(&mut session, "copy> ", &temp_file);
+    let screen = session.get_stream().screen();
+    let (row, _) = screen.cursor();
+    assert!((0..4).all(|col| screen.is_bold(row, col)));
+    assert!(!screen.is_bold(row, 4));
+    session.send("\x04")?;
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+    Ok(())
+}
+
+#[test]
+fn test_psql_copy_prompt_binary() -> Result<(), Box<dyn Error>> {
+    let (_backend, temp_file, mut session) = start("%l> ")?;
+
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line(r#"\copy t from '/dev/tty' (format binary)"#)?;
+    expect_screen!(&mut session, "End with an EOF signal.", &temp_file);
+    // Binary data has no lines to prompt for.
+    let prompted = wait_for_screen(&mut session, Duration::from_millis(500), |screen| {
+        !screen.before_cursor().is_empty()
+    });
+    if prompted {
+        report_session(&session.get_stream().screen(), temp_file.path());
+        panic!("psql prompted for binary COPY data");
+    }
+    session.send("\x04")?;
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+    Ok(())
+}
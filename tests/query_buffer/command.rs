@@ -0,0 +1,38 @@
+use crate::common::*;
+use std::error::Error;
+
+/// A query given with `-c` doesn't go into the query buffer or count as the
+/// last query sent, so the commands in later `-c` options have nothing to
+/// send.
+#[test]
+fn test_query_buffer_command_empty() -> Result<(), Box<dyn Error>> {
+    let dir = test_dir();
+    let output = psql_command()
+        .args(["-c", "SELECT 1 AS a;"])
+        .args(["-c", "\\g out.txt", "-c", "\\gx", "-c", "\\gset p_", "-c", "\\gexec", "-c", "\\gdesc"])
+        .args(["-c", "\\echo :{?p_a}"])
+        .current_dir(&dir)
+        .output()?;
+    expect_exit_status!(output, 0);
+    verify!(&output.stdout, " a \n---\n 1\n(1 row)\n\nFALSE\n");
+    isempty!(output.stderr);
+    assert!(!dir.join("out.txt").exists());
+    Ok(())
+}
+
+/// The server gets the whole `-c` string, backslash and all.
+#[test]
+fn test_query_buffer_command_inline() -> Result<(), Box<dyn Error>> {
+    for command in ["\\g out.txt", "\\gx", "\\gset", "\\gexec", "\\gdesc"] {
+        let output = run_cmd("psql", &["-X", "-c", &format!("SELECT 1 AS a {}", command)])?;
+        expect_exit_status!(output, 1);
+        verify!(
+            &output.stderr,
+            &format!(
+                "ERROR:  syntax error at or near \"\\\"\nLINE 1: SELECT 1 AS a {}\n                      ^\n",
+                command
+            )
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,47 @@
+//! The meta-commands that send the query buffer: `\g` to a file, a program
+//! or with one-off `\pset` options, `\gx`, `\gset`, `\gexec` and `\gdesc`.
+//! [`SCRIPT`] uses each of them; the `script` tests run it with `-f` and the
+//! `terminal` tests type the same lines into an interactive session. A `-c`
+//! string is sent to the server as it is and never fills the query buffer,
+//! which the `command` tests cover.
+//!
+//! Relative file names are in the test's directory, psql's working directory.
+
+mod command;
+mod script;
+mod terminal;
+
+/// Uses each of the commands, with `\gexec` running generated `COPY FROM
+/// STDIN` statements whose data follows in the script.
+pub const SCRIPT: &str = r#"SELECT 1 AS a, 'x' AS b \g out.txt
+\g
+SELECT 'abc' AS a \g |tr a-z A-Z
+SELECT 1 AS a, 'x,y' AS b \g (format=csv)
+SELECT 1 AS a, 'x' AS b \g (format=unaligned tuples_only fieldsep=|) out2.txt
+SELECT 1 AS a;
+SELECT 1 AS a, 'x' AS b \gx
+SELECT 1 AS a \gx (format=csv)
+SELECT 1 AS a, 'two' AS b, NULL AS c \gset p_
+\echo :p_a :p_b :{?p_c}
+SELECT 2 AS a FROM generate_series(1, 2) \gset p_
+SELECT 3 AS a WHERE false \gset p_
+\echo :p_a
+SELECT format('CREATE TABLE %I (n int, s text)', 'gexec_' || g) FROM generate_series(1, 2) g \gexec
+SELECT format('COPY %I FROM STDIN', 'gexec_' || g) FROM generate_series(1, 2) g \gexec
+1	a
+\.
+2	b
+\.
+SELECT format('COPY %I TO STDOUT', 'gexec_' || g) FROM generate_series(1, 2) g ORDER BY g \gexec
+SELECT 1 AS a, 'x'::text AS b, 1.5::numeric(3, 1) AS c \gdesc
+INSERT INTO gexec_1 VALUES (9, 'z') RETURNING n \gdesc
+SELECT count(*) FROM gexec_1;
+SELECT nope \gdesc
+\g
+"#;
+
+/// What `\g out.txt` writes: the aligned table psql would have printed.
+pub const OUT: &str = " a | b \n---+---\n 1 | x\n(1 row)\n\n";
+
+/// What `\g (format=unaligned tuples_only fieldsep=|) out2.txt` writes.
+pub const OUT2: &str = "1|x\n";
@@ -0,0 +1,80 @@
+use super::{OUT, OUT2, SCRIPT};
+use crate::common::*;
+use std::error::Error;
+use std::fs;
+
+#[test]
+fn test_query_buffer_script() -> Result<(), Box<dyn Error>> {
+    let dir = test_dir();
+    fs::write(dir.join("script.sql"), SCRIPT)?;
+    let output = psql_command().args(["-f", "script.sql"]).current_dir(&dir).output()?;
+    expect_exit_status!(output, 0);
+    verify!(
+        &output.stdout,
+        r#"
+ a | b 
+---+---
+ 1 | x
+(1 row)
+
+  A  
+-----
+ ABC
+(1 ROW)
+
+a,b
+1,"x,y"
+ a 
+---
+ 1
+(1 row)
+
+-[ RECORD 1 ]
+a | 1
+b | x
+
+a,1
+1 two FALSE
+1
+CREATE TABLE
+CREATE TABLE
+COPY 1
+COPY 1
+1	a
+2	b
+ Column |     Type     
+--------+--------------
+ a      | integer
+ b      | text
+ c      | numeric(3,1)
+(3 rows)
+
+ Column |  Type   
+--------+---------
+ n      | integer
+(1 row)
+
+ count 
+-------
+     1
+(1 row)
+
+"#
+    );
+    verify!(
+        &output.stderr,
+        r#"
+psql:script.sql:11: error: more than one row returned for \gset
+psql:script.sql:12: error: no rows returned for \gset
+psql:script.sql:24: ERROR:  column "nope" does not exist
+LINE 1: SELECT nope 
+               ^
+psql:script.sql:25: ERROR:  column "nope" does not exist
+LINE 1: SELECT nope 
+               ^
+"#
+    );
+    verify!(&fs::read(dir.join("out.txt"))?, OUT);
+    verify!(&fs::read(dir.join("out2.txt"))?, OUT2);
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use super::{OUT, OUT2};
+use crate::common::*;
+use expectrl::Eof;
+use std::error::Error;
+use std::fs;
+use tempfile::NamedTempFile;
+
+/// The lines of the script typed into an interactive session, where the
+/// data of the COPY statements `\gexec` runs comes from the terminal.
+#[test]
+fn test_query_buffer_terminal() -> Result<(), Box<dyn Error>> {
+    let dir = test_dir();
+    let temp_file = NamedTempFile::new()?;
+    let mut command = psql_command();
+    command.current_dir(&dir);
+    let mut session = spawn_session(command, temp_file.as_file())?;
+    set_timeout(&mut session, suite_timeout!());
+
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("SELECT 1 AS a, 'x' AS b \\g out.txt")?;
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("SELECT 1 AS a, 'x' AS b \\g (format=unaligned tuples_only fieldsep=|) out2.txt")?;
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    verify!(&fs::read(dir.join("out.txt"))?, OUT);
+    verify!(&fs::read(dir.join("out2.txt"))?, OUT2);
+
+    session.send_line("SELECT 'abc' AS a \\g |tr a-z A-Z")?;
+    expect_screen!(&mut session, "(1 ROW)", &temp_file);
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("SELECT 1 AS a, 'x,y' AS b \\g (format=csv)")?;
+    expect_screen!(&mut session, "1,\"x,y\"", &temp_file);
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("SELECT 1 AS a, 'x' AS b \\gx")?;
+    expect_screen!(&mut session, "-[ RECORD 1 ]", &temp_file);
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+
+    session.send_line("SELECT 1 AS a, 'two' AS b, NULL AS c \\gset p_")?;
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("\\echo :p_a :p_b :{?p_c}")?;
+    expect_screen!(&mut session, "1 two FALSE", &temp_file);
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("SELECT 2 AS a FROM generate_series(1, 2) \\gset p_")?;
+    expect_screen!(&mut session, "more than one row returned for \\gset", &temp_file);
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+
+    session.send_line("SELECT format('CREATE TABLE %I (n int, s text)', 'gexec_' || g) FROM generate_series(1, 2) g \\gexec")?;
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("SELECT format('COPY %I FROM STDIN', 'gexec_' || g) FROM generate_series(1, 2) g \\gexec")?;
+    for row in ["1\ta", "2\tb"] {
+        expect_prompt!(&mut session, PROMPT3, &temp_file);
+        session.send_line(row)?;
+        expect_prompt!(&mut session, PROMPT3, &temp_file);
+        session.send_line("\\.")?;
+    }
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("SELECT format('COPY %I TO STDOUT', 'gexec_' || g) FROM generate_series(1, 2) g ORDER BY g \\gexec")?;
+    // The screen shows tabs as the spaces up to the next tab stop.
+    expect_screen!(&mut session, "1       a\n2       b", &temp_file);
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+
+    session.send_line("INSERT INTO gexec_1 VALUES (9, 'z') RETURNING n \\gdesc")?;
+    expect_screen!(&mut session, " n      | integer", &temp_file);
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("SELECT count(*) FROM gexec_1;")?;
+    expect_screen!(&mut session, "     1\n(1 row)", &temp_file);
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+    Ok(())
+}
@@ -0,0 +1,151 @@
+//! Random tables of text values, exported with `\copy to` and imported again
+//! through each non-interactive input method: `\copy from` a file with `-c`,
+//! `\copy from stdin` in a `-f` script and in a script piped to psql, and
+//! `\copy from pstdin` with `-c` and the data piped. Values favor what COPY
+//! has to escape or quote: delimiters, line ends, backslashes, quotes, `\.`
+//! and `\N`, control characters and multibyte characters. On a mismatch
+//! proptest shrinks the table to a minimal failing one.
+//!
+//! Terminal input is left out, since the terminal's line discipline itself
+//! interprets control characters such as `^C`, `^D` and erase.
+
+use crate::common::*;
+use proptest::prelude::*;
+use proptest::test_runner::{Config, TestCaseError};
+use std::env;
+use std::fs;
+use uuid::Uuid;
+
+type Row = Vec<Option<String>>;
+
+/// Fragments values are built from. `\.` and `\N` come with and without
+/// line ends around them, since a line holding only `\.` ends COPY data.
+fn fragment() -> impl Strategy<Value = String> {
+    prop_oneof![
+        4 => "[a-z0-9 ]{1,4}",
+        1 => Just("\t".to_string()),
+        1 => Just("\n".to_string()),
+        1 => Just("\r\n".to_string()),
+        1 => Just("\r".to_string()),
+        1 => Just("\\".to_string()),
+        1 => Just("\"".to_string()),
+        1 => Just(",".to_string()),
+        1 => Just("\\.".to_string()),
+        1 => Just("\n\\.\n".to_string()),
+        1 => Just("\\N".to_string()),
+        1 => Just("\u{1}".to_string()),
+        1 => Just("\u{7f}".to_string()),
+        1 => Just("\u{8}\u{b}\u{c}".to_string()),
+        1 => Just("é日🐘".to_string()),
+    ]
+}
+
+fn value() -> impl Strategy<Value = Option<String>> {
+    proptest::option::weighted(0.9, proptest::collection::vec(fragment(), 0..5).prop_map(|fragments| fragments.concat()))
+}
+
+fn table() -> impl Strategy<Value = Vec<Row>> {
+    (1..=3usize).prop_flat_map(|columns| proptest::collection::vec(proptest::collection::vec(value(), columns), 1..5))
+}
+
+/// Cases per format, from `PROPTEST_CASES`; each case runs psql about a
+/// dozen times.
+fn config() -> Config {
+    let cases = env::var("PROPTEST_CASES").ok().and_then(|cases| cases.parse().ok()).unwrap_or(16);
+    Config {
+        cases,
+        // Failures are shrunk and printed rather than saved next to the
+        // sources.
+        failure_persistence: None,
+        ..Config::default()
+    }
+}
+
+fn psql(args: &[&str]) -> Result<Output, TestCaseError> {
+    run_cmd("psql", args).map_err(|err| TestCaseError::fail(err.to_string()))
+}
+
+fn psql_with_input(args: &[&str], input: &[u8]) -> Result<Output, TestCaseError> {
+    run_cmd_with_input("psql", args, input).map_err(|err| TestCaseError::fail(err.to_string()))
+}
+
+/// A table of text columns, dropped again when the case ends, including
+/// the failing and shrinking ones.
+struct Table(Uuid);
+
+impl Table {
+    fn create(columns: usize) -> Result<Self, TestCaseError> {
+        let table = Self(Uuid::new_v4());
+        let definition: Vec<String> = (1..=columns).map(|i| format!("c{} text", i)).collect();
+        let output = psql(&["-c", &format!(r#"CREATE TABLE "{}" ({});"#, table.0, definition.join(", "))])?;
+        expect_create_table!(output);
+        Ok(table)
+    }
+}
+
+impl Drop for Table {
+    fn drop(&mut self) {
+        let _ = run_cmd("psql", &["-c", &format!(r#"DROP TABLE IF EXISTS "{}";"#, self.0)]);
+    }
+}
+
+fn round_trip(format: &str, rows: &[Row]) -> Result<(), TestCaseError> {
+    let rows: Vec<Vec<Option<&str>>> = rows.iter().map(|row| row.iter().map(|value| value.as_deref()).collect()).collect();
+    let rows: Vec<&[Option<&str>]> = rows.iter().map(|row| row.as_slice()).collect();
+    let expected = PgCopy::text(&rows).to_bytes();
+    let columns = rows[0].len();
+    let count = format!("COPY {}\n", rows.len());
+
+    // Load the rows in binary, which needs no escaping, and export them in
+    // `format`.
+    let source = Table::create(columns)?;
+    let binary_path = test_dir().join(format!("{}.binary", source.0));
+    fs::write(&binary_path, &expected).map_err(|err| TestCaseError::fail(err.to_string()))?;
+    let output = psql(&["-c", &format!(r#"\copy "{}" from '{}' (format binary)"#, source.0, binary_path.display())])?;
+    expect_exit_status!(output, 0);
+    let path = test_dir().join(format!("{}.{}", source.0, format));
+    let output = psql(&["-c", &format!(r#"\copy "{}" to '{}' (format {})"#, source.0, path.display(), format)])?;
+    expect_exit_status!(output, 0);
+    verify!(output.stdout, &count);
+    let data = fs::read(&path).map_err(|err| TestCaseError::fail(err.to_string()))?;
+
+    let script_path = test_dir().join(format!("{}.sql", source.0));
+    for method in ["command", "script", "stdin", "pstdin"] {
+        let target = Table::create(columns)?;
+        let mut script = format!("\\copy \"{}\" from stdin (format {})\n", target.0, format).into_bytes();
+        script.extend_from_slice(&data);
+        let output = match method {
+            "command" => psql(&["-c", &format!(r#"\copy "{}" from '{}' (format {})"#, target.0, path.display(), format)])?,
+            "script" => {
+                fs::write(&script_path, &script).map_err(|err| TestCaseError::fail(err.to_string()))?;
+                psql(&["-f", &script_path.to_string_lossy()])?
+            }
+            "stdin" => psql_with_input(&[], &script)?,
+            _ => psql_with_input(&["-c", &format!(r#"\copy "{}" from pstdin (format {})"#, target.0, format)], &data)?,
+        };
+        expect_exit_status!(output, 0);
+        verify!(output.stdout, &count);
+        let output = psql(&["-c", &format!(r#"\copy "{}" to stdout (format binary)"#, target.0)])?;
+        verify_pgcopy!(output.stdout, &expected);
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(config())]
+
+    #[test]
+    fn test_round_trip_text(rows in table()) {
+        round_trip("text", &rows)?;
+    }
+
+    #[test]
+    fn test_round_trip_csv(rows in table()) {
+        round_trip("csv", &rows)?;
+    }
+
+    #[test]
+    fn test_round_trip_binary(rows in table()) {
+        round_trip("binary", &rows)?;
+    }
+}
@@ -0,0 +1,161 @@
+//! psql against a hot standby streaming from a primary, both private
+//! clusters of the harness. A standby is read-only, so `\copy from` fails
+//! there while `\copy to` works, and `target_session_attrs` picks one or the
+//! other from a list of hosts. Promoting the standby while psql is connected
+//! has a suite of its own.
+
+mod terminal_tty;
+
+use crate::common::cluster::{Cluster, DATABASE, SUPERUSER};
+use crate::common::*;
+use std::error::Error;
+use std::fs::{self, File};
+use uuid::Uuid;
+
+/// A new table of two int8 columns on `primary`, with the rows (1, 2) and
+/// (3, 4), once `standby` has it too.
+fn replicated_table(primary: &Cluster, standby: &Cluster) -> Result<Uuid, Box<dyn Error>> {
+    let table = Uuid::new_v4();
+    let output = primary.query(&format!(
+        "CREATE TABLE \"{0}\" (a int8, b int8); INSERT INTO \"{0}\" VALUES (1, 2), (3, 4);",
+        table
+    ))?;
+    expect_exit_status!(output, 0);
+    primary.wait_for_replay(standby)?;
+    Ok(table)
+}
+
+/// The primary and standby as a list of hosts, in that order, with
+/// `target_session_attrs`.
+fn host_list(first: &Cluster, second: &Cluster, target_session_attrs: &str) -> String {
+    format!(
+        "host={},{} port={},{} user={} dbname={} target_session_attrs={}",
+        first.data_dir().display(),
+        second.data_dir().display(),
+        first.port,
+        second.port,
+        SUPERUSER,
+        DATABASE,
+        target_session_attrs
+    )
+}
+
+#[test]
+fn test_standby_copy_from() -> Result<(), Box<dyn Error>> {
+    let (primary, standby) = Cluster::shared_with_standby();
+    let table = replicated_table(primary, standby)?;
+    let env = get_test_environment();
+    for format in ["text", "csv", "binary"] {
+        let path = match format {
+            "text" => &env.file_path_text,
+            "csv" => &env.file_path_csv,
+            _ => &env.file_path_binary,
+        };
+        let output = standby
+            .psql(SUPERUSER)
+            .args(["-c", &format!("\\copy \"{}\" from '{}' (format {})", table, path, format)])
+            .output()?;
+        expect_exit_status!(output, 1);
+        verify!(
+            &output.stderr,
+            "ERROR:  cannot execute COPY during recovery\n"
+        );
+    }
+
+    // The server refuses before COPY starts, so psql never reads the data
+    // that follows in a script; ON_ERROR_STOP keeps it from being run as SQL.
+    let script = test_dir().join("script.sql");
+    fs::write(
+        &script,
+        format!("\\copy \"{}\" from stdin\n{}\\.\n", table, fs::read_to_string(&env.file_path_text)?),
+    )?;
+    let output = standby
+        .psql(SUPERUSER)
+        .args(["-v", "ON_ERROR_STOP=1"])
+        .stdin(File::open(&script)?)
+        .output()?;
+    expect_exit_status!(output, 3);
+    verify!(
+        &output.stderr,
+        "ERROR:  cannot execute COPY during recovery\n"
+    );
+
+    let output = standby.psql(SUPERUSER).args(["-At", "-c", &format!("SELECT count(*) FROM \"{}\";", table)]).output()?;
+    expect_exit_status!(output, 0);
+    verify!(&output.stdout, "2\n");
+    Ok(())
+}
+
+#[test]
+fn test_standby_copy_to() -> Result<(), Box<dyn Error>> {
+    let (primary, standby) = Cluster::shared_with_standby();
+    let table = replicated_table(primary, standby)?;
+    let output = standby
+        .psql(SUPERUSER)
+        .args(["-c", &format!("\\copy \"{}\" to stdout", table)])
+        .args(["-c", &format!("\\copy (SELECT a + b FROM \"{}\" ORDER BY a) to stdout (format csv)", table)])
+        .output()?;
+    expect_exit_status!(output, 0);
+    verify!(&output.stdout, "1\t2\n3\t4\n3\n7\n");
+
+    let path = test_dir().join("out");
+    let output = standby
+        .psql(SUPERUSER)
+        .args(["-c", &format!("\\copy \"{}\" to '{}'", table, path.display())])
+        .output()?;
+    expect_exit_status!(output, 0);
+    verify!(&fs::read(&path)?, &fs::read_to_string(&get_test_environment().file_path_text)?);
+    Ok(())
+}
+
+/// psql goes to the first host of the kind asked for, whichever order the
+/// hosts are in.
+#[test]
+fn test_standby_target_session_attrs() -> Result<(), Box<dyn Error>> {
+    let (primary, standby) = Cluster::shared_with_standby();
+    for (first, second, target_session_attrs, expected) in [
+        (standby, primary, "any", standby),
+        (standby, primary, "read-write", primary),
+        (standby, primary, "primary", primary),
+        (primary, standby, "read-only", standby),
+        (primary, standby, "standby", standby),
+        (primary, standby, "prefer-standby", standby),
+        (standby, primary, "prefer-standby", standby),
+    ] {
+        let hosts = host_list(first, second, target_session_attrs);
+        println!("Connecting with {}", hosts);
+        let output = primary
+            .psql_defaults()
+            .args(["-d", &hosts, "-At", "-c", "SELECT pg_is_in_recovery(), current_setting('port');"])
+            .output()?;
+        expect_exit_status!(output, 0);
+        let in_recovery = if std::ptr::eq(expected, standby) { "t" } else { "f" };
+        verify!(&output.stdout, &format!("{}|{}\n", in_recovery, expected.port));
+    }
+    Ok(())
+}
+
+/// Writes to a standby are refused even when psql asks for a read-write
+/// session on it alone.
+#[test]
+fn test_standby_read_write_only_standby() -> Result<(), Box<dyn Error>> {
+    let (_, standby) = Cluster::shared_with_standby();
+    let output = standby
+        .psql_defaults()
+        .args([
+            "-d",
+            &format!(
+                "host={} port={} user={} dbname={} target_session_attrs=read-write",
+                standby.data_dir().display(),
+                standby.port,
+                SUPERUSER,
+                DATABASE
+            ),
+            "-c",
+            "SELECT 1;",
+        ])
+        .output()?;
+    expect_exit_status!(output, 2);
+    verify_contains!(&output.stderr, "session is read-only");
+    Ok(())
+}
@@ -0,0 +1,72 @@
+//! Interactive sessions while a standby is promoted. A session on the
+//! standby carries on and can write once the promotion is done. A session
+//! that asked for a read-write server loses its connection when the primary
+//! goes down, and psql's reset follows the host list to the promoted
+//! standby. Each test has a primary and standby of its own.
+
+use super::{host_list, replicated_table};
+use crate::common::cluster::{Cluster, SUPERUSER};
+use crate::common::*;
+use expectrl::Eof;
+use std::error::Error;
+use tempfile::NamedTempFile;
+
+fn start(mut command: std::process::Command) -> Result<(NamedTempFile, PtySession), Box<dyn Error>> {
+    command.args(["-v", "PROMPT1=%>%# "]);
+    let temp_file = NamedTempFile::new()?;
+    let mut session = spawn_session(command, temp_file.as_file())?;
+    set_timeout(&mut session, suite_timeout!());
+    Ok((temp_file, session))
+}
+
+#[test]
+fn test_standby_promote_mid_session() -> Result<(), Box<dyn Error>> {
+    let (primary, standby) = Cluster::start_with_standby()?;
+    let table = replicated_table(&primary, &standby)?;
+    let copy = format!("\\copy \"{}\" from '{}'", table, get_test_environment().file_path_text);
+    let prompt = format!("{}# ", standby.port);
+    let (temp_file, mut session) = start(standby.psql(SUPERUSER))?;
+
+    expect_prompt!(&mut session, &prompt, &temp_file);
+    session.send_line(&copy)?;
+    expect_screen!(&mut session, "cannot execute COPY during recovery", &temp_file);
+    expect_prompt!(&mut session, &prompt, &temp_file);
+
+    standby.promote()?;
+    session.send_line(&copy)?;
+    expect_screen!(&mut session, "COPY 2", &temp_file);
+    expect_prompt!(&mut session, &prompt, &temp_file);
+    session.send_line(format!("SELECT pg_is_in_recovery(), count(*) FROM \"{}\";", table))?;
+    expect_screen!(&mut session, " f                 |     4", &temp_file);
+    expect_prompt!(&mut session, &prompt, &temp_file);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+    Ok(())
+}
+
+#[test]
+fn test_standby_failover() -> Result<(), Box<dyn Error>> {
+    let (primary, standby) = Cluster::start_with_standby()?;
+    let table = replicated_table(&primary, &standby)?;
+    let mut command = primary.psql_defaults();
+    command.args(["-d", &host_list(&primary, &standby, "read-write")]);
+    let (temp_file, mut session) = start(command)?;
+
+    expect_prompt!(&mut session, &format!("{}# ", primary.port), &temp_file);
+    primary.crash()?;
+    standby.promote()?;
+    // The first statement only finds the connection closed; the next one
+    // finds it gone and resets it.
+    session.send_line("SELECT 1;")?;
+    expect_screen!(&mut session, "server closed the connection unexpectedly", &temp_file);
+    session.send_line("SELECT 2;")?;
+    expect_screen!(&mut session, "The connection to the server was lost. Attempting reset: Succeeded.", &temp_file);
+    let prompt = format!("{}# ", standby.port);
+    expect_prompt!(&mut session, &prompt, &temp_file);
+    session.send_line(format!("\\copy \"{}\" from '{}'", table, get_test_environment().file_path_text))?;
+    expect_screen!(&mut session, "COPY 2", &temp_file);
+    expect_prompt!(&mut session, &prompt, &temp_file);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+    Ok(())
+}
@@ -0,0 +1,2 @@
+mod terminal_stdin;
+mod terminal_tty;
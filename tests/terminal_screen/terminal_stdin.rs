@@ -0,0 +1,77 @@
+use crate::common::mock::MockBackend;
+use crate::common::*;
+use expectrl::Eof;
+use std::error::Error;
+
+#[test]
+fn test_psql_copy_screen() -> Result<(), Box<dyn Error>> {
+    let backend = MockBackend::start()?;
+
+    let temp_file = tempfile::NamedTempFile::new()?;
+    let log_file = temp_file.as_file();
+
+    let mut command = psql_command();
+    command.args(backend.psql_args());
+    let mut session = spawn_session(command, log_file)?;
+
+    set_timeout(&mut session, suite_timeout!());
+
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line(r#"\copy t from stdin"#)?;
+    expect_prompt!(&mut session, PROMPT3, &temp_file);
+    session.send_line("1\t2")?;
+    expect_prompt!(&mut session, PROMPT3, &temp_file);
+    session.send_line("3\t4")?;
+    expect_prompt!(&mut session, PROMPT3, &temp_file);
+
+    // Each data line is echoed after its own prompt, directly below the
+    // instructions, and the cursor waits right after the next prompt.
+    let screen = session.get_stream().screen();
+    let top = screen.find_row(r"\copy t from stdin").unwrap();
+    verify!(screen.text_from(top).into_bytes(), r#"
+psql_tester1> \copy t from stdin
+Enter data to be copied followed by a newline.
+End with a backslash and a period on a line by itself, or an EOF signal.
+psql_tester3> 1 2
+psql_tester3> 3 4
+psql_tester3>
+"#);
+    assert_eq!(screen.cursor(), (top + 5, PROMPT3.len() as u16));
+
+    session.send_line("\\.")?;
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    let screen = session.get_stream().screen();
+    assert_eq!(screen.find_row("COPY 2"), Some(top + 6));
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+    Ok(())
+}
+
+#[test]
+fn test_psql_copy_screen_cancel() -> Result<(), Box<dyn Error>> {
+    let backend = MockBackend::start()?;
+
+    let temp_file = tempfile::NamedTempFile::new()?;
+    let log_file = temp_file.as_file();
+
+    let mut command = psql_command();
+    command.args(backend.psql_args());
+    let mut session = spawn_session(command, log_file)?;
+
+    set_timeout(&mut session, suite_timeout!());
+
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line(r#"\copy t from stdin"#)?;
+    expect_prompt!(&mut session, PROMPT3, &temp_file);
+    session.send("\x03")?;
+    expect_screen!(&mut session, "COPY from stdin failed: canceled by user", &temp_file);
+
+    // The regular prompt comes back at the start of a fresh row.
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    let screen = session.get_stream().screen();
+    let (row, _) = screen.cursor();
+    assert!(screen.find_row(PROMPT3.trim_end()).unwrap() < row);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+    Ok(())
+}
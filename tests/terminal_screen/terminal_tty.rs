@@ -0,0 +1,42 @@
+use crate::common::mock::MockBackend;
+use crate::common::*;
+use expectrl::Eof;
+use std::error::Error;
+
+#[test]
+fn test_psql_copy_screen() -> Result<(), Box<dyn Error>> {
+    let backend = MockBackend::start()?;
+
+    let temp_file = tempfile::NamedTempFile::new()?;
+    let log_file = temp_file.as_file();
+
+    let mut command = psql_command();
+    command.args(backend.psql_args());
+    let mut session = spawn_session(command, log_file)?;
+
+    set_timeout(&mut session, suite_timeout!());
+
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    session.send_line(r#"\copy t from '/dev/tty'"#)?;
+    expect_prompt!(&mut session, PROMPT3, &temp_file);
+    session.send_line("1\t2")?;
+    expect_prompt!(&mut session, PROMPT3, &temp_file);
+    session.send("\x04")?;
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+
+    // ^D echoes nothing, so the command tag follows the last prompt on the
+    // same row.
+    let screen = session.get_stream().screen();
+    let top = screen.find_row(r"\copy t from '/dev/tty'").unwrap();
+    verify!(screen.text_from(top).into_bytes(), r#"
+psql_tester1> \copy t from '/dev/tty'
+Enter data to be copied followed by a newline.
+End with an EOF signal.
+psql_tester3> 1 2
+psql_tester3> COPY 1
+psql_tester1>
+"#);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+    Ok(())
+}
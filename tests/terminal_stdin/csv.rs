@@ -1,15 +1,9 @@
 use crate::common::*;
-use expectrl::{session, spawn, Eof};
-use similar::{ChangeTag, TextDiff};
-use std::borrow::Cow;
+use expectrl::Eof;
 use std::error::Error;
-use std::io::Write;
-use std::time::Duration;
-use termcolor::{ColorChoice, ColorSpec, StandardStream, WriteColor};
 use uuid::Uuid;
 
-#[test]
-fn test_psql_copy() -> Result<(), Box<dyn Error>> {
+fn copy(profile: Profile) -> Result<(), Box<dyn Error>> {
     let test_table = Uuid::new_v4();
     let output = run_cmd("psql", &["-c", &format!(r#"CREATE TABLE "{}" (c1 int8, c2 int8);"#, test_table)])?;
     expect_create_table!(output);
@@ -17,25 +11,23 @@ fn test_psql_copy() -> Result<(), Box<dyn Error>> {
     let temp_file = tempfile::NamedTempFile::new()?;
     let log_file = temp_file.as_file();
 
-    let mut session = session::log(spawn("psql")?, log_file.try_clone()?)?;
+    let mut session = spawn_session(profile.command(), log_file)?;
 
-    session.set_expect_timeout(Some(Duration::from_secs(1)));
+    set_timeout(&mut session, suite_timeout!());
 
-    let database_name =
-        std::env::var("PGDATABASE").unwrap_or_else(|_| std::env::var("USER").unwrap());
-
-    expect!(&mut session, &format!("{}=#", database_name), &temp_file);
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
+    #[allow(clippy::needless_borrows_for_generic_args)]
     session.send_line(&format!(r#"\copy "{}" from stdin (format csv)"#, test_table))?;
     expect!(&mut session, "Enter data to be copied followed by a newline.", &temp_file);
     expect!(&mut session, "End with a backslash and a period on a line by itself, or an EOF signal.", &temp_file);
-    expect!(&mut session, ">>", &temp_file);
+    expect_prompt!(&mut session, PROMPT3, &temp_file);
     session.send_line("1,2")?;
-    expect!(&mut session, ">>", &temp_file);
+    expect_prompt!(&mut session, PROMPT3, &temp_file);
     session.send_line("3,4")?;
-    expect!(&mut session, ">>", &temp_file);
+    expect_prompt!(&mut session, PROMPT3, &temp_file);
     session.send_line("\\.")?;
     expect!(&mut session, "COPY 2", &temp_file);
-    expect!(&mut session, &format!("{}=#", database_name), &temp_file);
+    expect_prompt!(&mut session, PROMPT1, &temp_file);
     session.send_line("\\q")?;
     session.expect(Eof)?;
 
@@ -44,4 +36,14 @@ fn test_psql_copy() -> Result<(), Box<dyn Error>> {
     let output = run_cmd("psql", &["-c", &format!(r#"DROP TABLE "{}";"#, test_table)])?;
     expect_drop_table!(output);
     Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_psql_copy() -> Result<(), Box<dyn Error>> {
+    Cell::terminal("stdin", "csv", Profile::detected()).run(|| copy(Profile::detected()))
+}
+
+#[test]
+fn test_psql_copy_no_readline() -> Result<(), Box<dyn Error>> {
+    Cell::terminal("stdin", "csv", Profile::NoReadline).run(|| copy(Profile::NoReadline))
+}
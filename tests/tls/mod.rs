@@ -0,0 +1,183 @@
+//! TLS connections to the harness's private cluster, which has `ssl = on`
+//! and certificates signed by a CA of its own; see
+//! [`Cluster::ssl_dir`]. The tests check which `sslmode` values end up
+//! encrypted, when the server's certificate is checked, client certificates
+//! and what `\conninfo` says. Large `\copy` transfers have a suite of their
+//! own.
+
+mod copy;
+
+use crate::common::cluster::{Cluster, Method, SUPERUSER};
+use crate::common::*;
+use std::error::Error;
+use std::fs::{self, Permissions};
+use std::os::unix::fs::PermissionsExt;
+use std::process::{Command, Output};
+
+/// Whether the connection of `command` is encrypted, from the server.
+fn ssl(command: &mut Command) -> Result<Output, Box<dyn Error>> {
+    Ok(command
+        .args(["-w", "-At", "-c", "SELECT ssl FROM pg_stat_ssl WHERE pid = pg_backend_pid();"])
+        .output()?)
+}
+
+#[test]
+fn test_tls_sslmodes() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    // allow tries without TLS first, prefer with it first.
+    for (sslmode, encrypted) in [
+        ("disable", "f"),
+        ("allow", "f"),
+        ("prefer", "t"),
+        ("require", "t"),
+        ("verify-ca", "t"),
+        ("verify-full", "t"),
+    ] {
+        println!("sslmode={}", sslmode);
+        let output = ssl(&mut cluster.psql_tcp(SUPERUSER, sslmode))?;
+        expect_exit_status!(output, 0);
+        verify!(&output.stdout, &format!("{}\n", encrypted));
+    }
+    Ok(())
+}
+
+/// pg_hba.conf has only a `hostssl` line for the cert role, so `disable`
+/// fails and `allow` falls back to TLS.
+#[test]
+fn test_tls_required_by_server() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    let ssl_dir = cluster.ssl_dir();
+    let certificate = |sslmode| {
+        let mut command = cluster.psql_tcp(Method::Cert.role(), sslmode);
+        command
+            .env("PGSSLCERT", ssl_dir.join("client.crt"))
+            .env("PGSSLKEY", ssl_dir.join("client.key"));
+        command
+    };
+
+    let output = ssl(&mut certificate("disable"))?;
+    expect_exit_status!(output, 2);
+    verify_contains!(
+        &output.stderr,
+        &format!("no pg_hba.conf entry for host \"127.0.0.1\", user \"{}\"", Method::Cert.role())
+    );
+    verify_contains!(&output.stderr, "no encryption");
+
+    let output = ssl(&mut certificate("allow"))?;
+    expect_exit_status!(output, 0);
+    verify!(&output.stdout, "t\n");
+    Ok(())
+}
+
+/// A root certificate that didn't sign the server's certificate fails every
+/// mode that checks it. libpq checks it for `require` and `prefer` too
+/// whenever the root certificate file exists.
+#[test]
+fn test_tls_wrong_root_certificate() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    // Signed by the CA, but not a CA itself.
+    let wrong = cluster.ssl_dir().join("client.crt");
+    for sslmode in ["require", "verify-ca", "verify-full"] {
+        println!("sslmode={}", sslmode);
+        let mut command = cluster.psql_tcp(SUPERUSER, sslmode);
+        command.env("PGSSLROOTCERT", &wrong);
+        let output = ssl(&mut command)?;
+        expect_exit_status!(output, 2);
+        verify_contains!(&output.stderr, "certificate verify failed");
+    }
+
+    // prefer gives up on TLS and connects without it.
+    let output = ssl(cluster.psql_tcp(SUPERUSER, "prefer").env("PGSSLROOTCERT", &wrong))?;
+    expect_exit_status!(output, 0);
+    verify!(&output.stdout, "f\n");
+    Ok(())
+}
+
+/// Without a root certificate file, `require` encrypts without checking,
+/// and the modes that check refuse to connect.
+#[test]
+fn test_tls_missing_root_certificate() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    let missing = test_dir().join("root.crt");
+    let output = ssl(cluster.psql_tcp(SUPERUSER, "require").env("PGSSLROOTCERT", &missing))?;
+    expect_exit_status!(output, 0);
+    verify!(&output.stdout, "t\n");
+
+    for sslmode in ["verify-ca", "verify-full"] {
+        println!("sslmode={}", sslmode);
+        let output = ssl(cluster.psql_tcp(SUPERUSER, sslmode).env("PGSSLROOTCERT", &missing))?;
+        expect_exit_status!(output, 2);
+        verify_contains!(
+            &output.stderr,
+            &format!("root certificate file \"{}\" does not exist", missing.display())
+        );
+    }
+    Ok(())
+}
+
+/// `verify-full` checks the host name against the certificate, which names
+/// `localhost` and `127.0.0.1`; `verify-ca` doesn't.
+#[test]
+fn test_tls_host_name() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    for (sslmode, host, status) in [
+        ("verify-full", "localhost", 0),
+        ("verify-full", "other.invalid", 2),
+        ("verify-ca", "other.invalid", 0),
+    ] {
+        println!("sslmode={} host={}", sslmode, host);
+        let mut command = cluster.psql_tcp(SUPERUSER, sslmode);
+        command.args(["-h", host]).env("PGHOSTADDR", "127.0.0.1");
+        let output = ssl(&mut command)?;
+        expect_exit_status!(output, status);
+        if status != 0 {
+            verify_contains!(
+                &output.stderr,
+                "server certificate for \"localhost\" (and 1 other name) does not match host name \"other.invalid\""
+            );
+        }
+    }
+    Ok(())
+}
+
+/// libpq won't use a client key others can read.
+#[test]
+fn test_tls_client_key_permissions() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    let key = test_dir().join("client.key");
+    fs::copy(cluster.ssl_dir().join("client.key"), &key)?;
+    fs::set_permissions(&key, Permissions::from_mode(0o644))?;
+    let output = ssl(cluster.psql_tls(Method::Cert.role(), true).env("PGSSLKEY", &key))?;
+    expect_exit_status!(output, 2);
+    verify_contains!(
+        &output.stderr,
+        &format!("private key file \"{}\" has group or world access", key.display())
+    );
+    Ok(())
+}
+
+/// `\conninfo` adds a line about TLS to an encrypted connection's.
+#[test]
+fn test_tls_conninfo() -> Result<(), Box<dyn Error>> {
+    let cluster = Cluster::shared();
+    let connected = format!(
+        "You are connected to database \"postgres\" as user \"{}\" on host \"127.0.0.1\" at port \"{}\".\n",
+        SUPERUSER, cluster.port
+    );
+
+    let output = cluster.psql_tcp(SUPERUSER, "disable").args(["-c", "\\conninfo"]).output()?;
+    expect_exit_status!(output, 0);
+    verify!(&output.stdout, &connected);
+
+    let output = cluster.psql_tcp(SUPERUSER, "verify-full").args(["-c", "\\conninfo"]).output()?;
+    expect_exit_status!(output, 0);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (first, ssl) = stdout.split_at(connected.len().min(stdout.len()));
+    verify!(first, &connected);
+    assert!(
+        ssl.starts_with("SSL connection (protocol: TLSv1.") && ssl.ends_with(", compression: off)\n"),
+        "unexpected \\conninfo: {}",
+        stdout
+    );
+    Ok(())
+}
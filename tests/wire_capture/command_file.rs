@@ -0,0 +1,38 @@
+use crate::common::proxy::Proxy;
+use crate::common::wire::{self, Sender};
+use crate::common::*;
+use std::error::Error;
+use std::fs;
+use uuid::Uuid;
+
+#[test]
+fn test_psql_copy_chunking() -> Result<(), Box<dyn Error>> {
+    let test_table = Uuid::new_v4();
+    let output = run_cmd("psql", &["-c", &format!(r#"CREATE TABLE "{}" (c1 int8, c2 int8);"#, test_table)])?;
+    expect_create_table!(output);
+    let large_file = test_dir().join(format!("{}.text", test_table));
+    write_series_file(&large_file, 10000)?;
+
+    let proxy = Proxy::start(None)?;
+    let output = run_cmd("psql", &["-h", proxy.host(), "-p", &proxy.port(), "-c", &format!(r#"\copy "{}" from '{}'"#, test_table, large_file.display())])?;
+    expect_exit_status!(output, 0);
+    verify!(output.stdout, "\nCOPY 10000\n");
+
+    // The file goes out unchanged, in chunks of at most psql's COPYBUFSIZ.
+    let copy_data = proxy.copy_data();
+    assert!(copy_data.len() > 1);
+    assert!(copy_data.iter().all(|chunk| chunk.len() <= 8192));
+    assert_eq!(copy_data.concat(), fs::read(&large_file)?);
+    assert_eq!(proxy.copy_end().and_then(|m| m.tag), Some(wire::COPY_DONE));
+    let completed: Vec<String> = proxy
+        .captured()
+        .iter()
+        .filter(|c| c.sender == Sender::Backend && c.message.tag == Some(wire::COMMAND_COMPLETE))
+        .map(|c| wire::cstring(&c.message.body))
+        .collect();
+    assert_eq!(completed, vec!["COPY 10000"]);
+
+    let output = run_cmd("psql", &["-c", &format!(r#"DROP TABLE "{}";"#, test_table)])?;
+    expect_drop_table!(output);
+    Ok(())
+}
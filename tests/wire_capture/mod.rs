@@ -0,0 +1,4 @@
+mod command_file;
+mod script_stdin;
+mod terminal_stdin;
+mod terminal_tty;
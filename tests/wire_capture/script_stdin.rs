@@ -0,0 +1,61 @@
+use crate::common::proxy::Proxy;
+use crate::common::wire::{self, Sender};
+use crate::common::*;
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+use uuid::Uuid;
+
+fn run_script(proxy: &Proxy, test_table: &Uuid, data: &str) -> Result<Output, Box<dyn Error>> {
+    let test_file_path = test_dir().join(format!("{}", test_table));
+    let mut test_file = File::create(&test_file_path)?;
+    writeln!(test_file, r#"\copy "{}" from stdin"#, test_table)?;
+    write!(test_file, "{}", data)?;
+    Ok(run_cmd("psql", &["-h", proxy.host(), "-p", &proxy.port(), "-f", &test_file_path.to_string_lossy()])?)
+}
+
+#[test]
+fn test_psql_copy_terminator() -> Result<(), Box<dyn Error>> {
+    let test_table = Uuid::new_v4();
+    let output = run_cmd("psql", &["-c", &format!(r#"CREATE TABLE "{}" (c1 int8, c2 int8);"#, test_table)])?;
+    expect_create_table!(output);
+
+    let proxy = Proxy::start(None)?;
+    let output = run_script(&proxy, &test_table, "1\t2\n3\t4\n\\.\n")?;
+    expect_copy_two!(output);
+    // The end-of-data marker is forwarded to the server along with the data.
+    assert_eq!(proxy.copy_data(), vec![b"1\t2\n3\t4\n\\.\n".to_vec()]);
+    assert_eq!(proxy.copy_end().and_then(|m| m.tag), Some(wire::COPY_DONE));
+
+    let output = run_cmd("psql", &["-c", &format!(r#"SELECT * FROM "{}";"#, test_table)])?;
+    expect_result_set!(output);
+    let output = run_cmd("psql", &["-c", &format!(r#"DROP TABLE "{}";"#, test_table)])?;
+    expect_drop_table!(output);
+    Ok(())
+}
+
+#[test]
+fn test_psql_copy_eof() -> Result<(), Box<dyn Error>> {
+    let test_table = Uuid::new_v4();
+    let output = run_cmd("psql", &["-c", &format!(r#"CREATE TABLE "{}" (c1 int8, c2 int8);"#, test_table)])?;
+    expect_create_table!(output);
+
+    let proxy = Proxy::start(None)?;
+    let output = run_script(&proxy, &test_table, "1\t2\n3\t4\n")?;
+    expect_copy_two!(output);
+    // Reaching the end of the script ends the COPY with CopyDone, not CopyFail.
+    assert_eq!(proxy.copy_data(), vec![b"1\t2\n3\t4\n".to_vec()]);
+    assert_eq!(proxy.copy_end().and_then(|m| m.tag), Some(wire::COPY_DONE));
+    let errors = proxy
+        .captured()
+        .into_iter()
+        .filter(|c| c.sender == Sender::Backend && c.message.tag == Some(wire::ERROR_RESPONSE))
+        .count();
+    assert_eq!(errors, 0);
+
+    let output = run_cmd("psql", &["-c", &format!(r#"SELECT * FROM "{}";"#, test_table)])?;
+    expect_result_set!(output);
+    let output = run_cmd("psql", &["-c", &format!(r#"DROP TABLE "{}";"#, test_table)])?;
+    expect_drop_table!(output);
+    Ok(())
+}
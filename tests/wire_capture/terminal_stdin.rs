@@ -0,0 +1,51 @@
+use crate::common::proxy::Proxy;
+use crate::common::wire::{self, Sender};
+use crate::common::*;
+use expectrl::Eof;
+use std::error::Error;
+use std::io::Write;
+use uuid::Uuid;
+
+#[test]
+fn test_psql_copy_cancel() -> Result<(), Box<dyn Error>> {
+    let test_table = Uuid::new_v4();
+    let output = run_cmd("psql", &["-c", &format!(r#"CREATE TABLE "{}" (c1 int8, c2 int8);"#, test_table)])?;
+    expect_create_table!(output);
+
+    let proxy = Proxy::start(None)?;
+
+    let temp_file = tempfile::NamedTempFile::new()?;
+    let log_file = temp_file.as_file();
+
+    let mut command = psql_command();
+    command.args(["-h", proxy.host(), "-p", &proxy.port()]);
+    let mut session = spawn_session(command, log_file)?;
+
+    set_timeout(&mut session, suite_timeout!());
+
+    expect_prompt!(&mut session, PROMPT1, &temp_file, proxy);
+    session.send_line(format!(r#"\copy "{}" from stdin"#, test_table))?;
+    expect_prompt!(&mut session, PROMPT3, &temp_file, proxy);
+    session.send_line("1\t2")?;
+    expect_prompt!(&mut session, PROMPT3, &temp_file, proxy);
+    write!(session, "\x03")?;
+    expect!(&mut session, "canceled by user", &temp_file, proxy);
+    expect_prompt!(&mut session, PROMPT1, &temp_file, proxy);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+
+    // The canceled COPY is ended with CopyFail, and the server reports it back.
+    let copy_end = proxy.copy_end().unwrap();
+    assert_eq!(copy_end.tag, Some(wire::COPY_FAIL));
+    assert_eq!(wire::cstring(&copy_end.body), "canceled by user");
+    let error = proxy
+        .captured()
+        .into_iter()
+        .find(|c| c.sender == Sender::Backend && c.message.tag == Some(wire::ERROR_RESPONSE))
+        .unwrap();
+    verify_contains!(error.message.describe(Sender::Backend), "ERROR 57014");
+
+    let output = run_cmd("psql", &["-c", &format!(r#"DROP TABLE "{}";"#, test_table)])?;
+    expect_drop_table!(output);
+    Ok(())
+}
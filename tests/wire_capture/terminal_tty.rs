@@ -0,0 +1,48 @@
+use crate::common::proxy::Proxy;
+use crate::common::wire;
+use crate::common::*;
+use expectrl::Eof;
+use std::error::Error;
+use std::io::Write;
+use uuid::Uuid;
+
+#[test]
+fn test_psql_copy_eof() -> Result<(), Box<dyn Error>> {
+    let test_table = Uuid::new_v4();
+    let output = run_cmd("psql", &["-c", &format!(r#"CREATE TABLE "{}" (c1 int8, c2 int8);"#, test_table)])?;
+    expect_create_table!(output);
+
+    let proxy = Proxy::start(None)?;
+
+    let temp_file = tempfile::NamedTempFile::new()?;
+    let log_file = temp_file.as_file();
+
+    let mut command = psql_command();
+    command.args(["-h", proxy.host(), "-p", &proxy.port()]);
+    let mut session = spawn_session(command, log_file)?;
+
+    set_timeout(&mut session, suite_timeout!());
+
+    expect_prompt!(&mut session, PROMPT1, &temp_file, proxy);
+    session.send_line(format!(r#"\copy "{}" from '/dev/tty'"#, test_table))?;
+    expect_prompt!(&mut session, PROMPT3, &temp_file, proxy);
+    session.send_line("1\t2")?;
+    expect_prompt!(&mut session, PROMPT3, &temp_file, proxy);
+    session.send_line("3\t4")?;
+    expect_prompt!(&mut session, PROMPT3, &temp_file, proxy);
+    write!(session, "\x04")?;
+    expect!(&mut session, "COPY 2", &temp_file, proxy);
+    expect_prompt!(&mut session, PROMPT1, &temp_file, proxy);
+    session.send_line("\\q")?;
+    session.expect(Eof)?;
+
+    // An EOF from the terminal ends the COPY with CopyDone after the data.
+    assert_eq!(proxy.copy_data().concat(), b"1\t2\n3\t4\n");
+    assert_eq!(proxy.copy_end().and_then(|m| m.tag), Some(wire::COPY_DONE));
+
+    let output = run_cmd("psql", &["-c", &format!(r#"SELECT * FROM "{}";"#, test_table)])?;
+    expect_result_set!(output);
+    let output = run_cmd("psql", &["-c", &format!(r#"DROP TABLE "{}";"#, test_table)])?;
+    expect_drop_table!(output);
+    Ok(())
+}